@@ -0,0 +1,344 @@
+//! Machine-readable diagnostics, one JSON object per line.
+//!
+//! The schema follows `rustc --error-format=json` so existing tooling can consume it:
+//!
+//! ```json
+//! {
+//!   "$message_type": "diagnostic",
+//!   "message": "unterminated string literal",
+//!   "code": { "code": "E0011", "explanation": null },
+//!   "level": "error",
+//!   "spans": [{
+//!     "file_name": "main.osta", "byte_start": 8, "byte_end": 13,
+//!     "line_start": 1, "line_end": 1, "column_start": 9, "column_end": 14,
+//!     "is_primary": true,
+//!     "text": [{ "text": "const a \"open", "highlight_start": 9, "highlight_end": 14 }],
+//!     "label": "missing closing `\"`", "suggested_replacement": null,
+//!     "suggestion_applicability": null, "expansion": null
+//!   }],
+//!   "children": [{
+//!     "message": "close the string", "code": null, "level": "help", "spans": [...],
+//!     "children": [], "rendered": null
+//!   }],
+//!   "rendered": "error[E0011]: unterminated string literal\n..."
+//! }
+//! ```
+//!
+//! Lines and columns are one-based, with columns counted in characters. Notes, help and
+//! suggestions become `children`; a suggestion carries its edit in `suggested_replacement`, with
+//! an `Unspecified` applicability. Fields osta has no value for, such as `explanation` and
+//! `expansion`, are always `null`.
+
+use crate::diagnostic::Diagnostic;
+use crate::render::Renderer;
+use osta_lexer::{Encoding, LineIndex, Span};
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    #[serde(rename = "$message_type")]
+    message_type: &'static str,
+    message: &'a str,
+    code: Option<JsonCode>,
+    level: &'static str,
+    spans: Vec<JsonSpan<'a>>,
+    children: Vec<JsonChild<'a>>,
+    rendered: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonCode {
+    code: &'static str,
+    explanation: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct JsonChild<'a> {
+    message: &'a str,
+    code: Option<JsonCode>,
+    level: &'static str,
+    spans: Vec<JsonSpan<'a>>,
+    children: Vec<JsonChild<'a>>,
+    rendered: Option<String>,
+}
+
+impl<'a> JsonChild<'a> {
+    fn new(message: &'a str, level: &'static str, spans: Vec<JsonSpan<'a>>) -> Self {
+        Self {
+            message,
+            code: None,
+            level,
+            spans,
+            children: Vec::new(),
+            rendered: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonSpan<'a> {
+    file_name: &'a str,
+    byte_start: usize,
+    byte_end: usize,
+    line_start: usize,
+    line_end: usize,
+    column_start: usize,
+    column_end: usize,
+    is_primary: bool,
+    text: Vec<JsonSpanLine<'a>>,
+    label: Option<&'a str>,
+    suggested_replacement: Option<&'a str>,
+    suggestion_applicability: Option<&'static str>,
+    expansion: Option<()>,
+}
+
+/// A source line covered by a span, with the highlighted columns.
+#[derive(Serialize)]
+struct JsonSpanLine<'a> {
+    text: &'a str,
+    highlight_start: usize,
+    highlight_end: usize,
+}
+
+/// Serializes diagnostics in the `rustc` JSON format described in the module docs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonEmitter {
+    rendered: bool,
+}
+
+impl JsonEmitter {
+    pub fn new() -> Self {
+        Self { rendered: true }
+    }
+
+    /// Sets whether the plain-text rendering is included in the `rendered` field.
+    pub fn with_rendered(mut self, rendered: bool) -> Self {
+        self.rendered = rendered;
+        self
+    }
+
+    /// Serializes `diagnostic` against the source in `index`, which was read from `path`.
+    pub fn to_json(&self, diagnostic: &Diagnostic, index: &LineIndex, path: &str) -> String {
+        let span = |span: Span, is_primary, label, suggested_replacement| {
+            json_span(index, path, span, is_primary, label, suggested_replacement)
+        };
+
+        let spans = diagnostic
+            .labels
+            .iter()
+            .enumerate()
+            .map(|(i, (s, label))| span(*s, i == 0, Some(label.as_str()), None))
+            .collect();
+
+        let mut children: Vec<_> = diagnostic
+            .notes
+            .iter()
+            .map(|note| JsonChild::new(note, "note", Vec::new()))
+            .collect();
+        children.extend(
+            diagnostic
+                .help
+                .iter()
+                .map(|help| JsonChild::new(help, "help", Vec::new())),
+        );
+        children.extend(diagnostic.suggestions.iter().map(|suggestion| {
+            let replacement = Some(suggestion.replacement.as_str());
+            let spans = vec![span(suggestion.span, true, None, replacement)];
+            JsonChild::new(&suggestion.message, "help", spans)
+        }));
+
+        let json = JsonDiagnostic {
+            message_type: "diagnostic",
+            message: &diagnostic.message,
+            code: diagnostic.code.map(|code| JsonCode {
+                code,
+                explanation: None,
+            }),
+            level: diagnostic.severity.as_str(),
+            spans,
+            children,
+            rendered: self
+                .rendered
+                .then(|| Renderer::plain().render(diagnostic, index, path)),
+        };
+        serde_json::to_string(&json).expect("diagnostics always serialize")
+    }
+
+    /// Writes `diagnostic` to `writer` as a single line of JSON.
+    pub fn emit(
+        &self,
+        writer: &mut impl Write,
+        diagnostic: &Diagnostic,
+        index: &LineIndex,
+        path: &str,
+    ) -> io::Result<()> {
+        writeln!(writer, "{}", self.to_json(diagnostic, index, path))
+    }
+}
+
+impl Default for JsonEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn json_span<'a>(
+    index: &LineIndex<'a>,
+    file_name: &'a str,
+    span: Span,
+    is_primary: bool,
+    label: Option<&'a str>,
+    suggested_replacement: Option<&'a str>,
+) -> JsonSpan<'a> {
+    let start = index
+        .line_col(span.start, Encoding::Utf32)
+        .unwrap_or_default();
+    let end = index.line_col(span.end, Encoding::Utf32).unwrap_or(start);
+    let text = (start.line..=end.line)
+        .filter_map(|line| {
+            let text = index.line(line)?;
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            Some(JsonSpanLine {
+                text,
+                highlight_start: if line == start.line { start.col + 1 } else { 1 },
+                highlight_end: if line == end.line {
+                    end.col + 1
+                } else {
+                    text.chars().count() + 1
+                },
+            })
+        })
+        .collect();
+    JsonSpan {
+        file_name,
+        byte_start: span.start,
+        byte_end: span.end,
+        line_start: start.line + 1,
+        line_end: end.line + 1,
+        column_start: start.col + 1,
+        column_end: end.col + 1,
+        is_primary,
+        text,
+        label,
+        suggested_replacement,
+        suggestion_applicability: suggested_replacement.map(|_| "Unspecified"),
+        expansion: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonEmitter;
+    use crate::diagnostic::Diagnostic;
+    use osta_lexer::{Lexer, LineIndex, Span};
+    use serde_json::{Value, json};
+
+    #[test]
+    fn schema() {
+        let source = "const é\nconst a \"open";
+        let index = LineIndex::new(source);
+        let error = Lexer::new(source).find_map(Result::err).unwrap();
+        let diagnostic = Diagnostic::from(error);
+
+        let emitter = JsonEmitter::new().with_rendered(false);
+        let json: Value =
+            serde_json::from_str(&emitter.to_json(&diagnostic, &index, "a.osta")).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "$message_type": "diagnostic",
+                "message": "unterminated string literal",
+                "code": { "code": "E0011", "explanation": null },
+                "level": "error",
+                "spans": [{
+                    "file_name": "a.osta",
+                    "byte_start": 17,
+                    "byte_end": 22,
+                    "line_start": 2,
+                    "line_end": 2,
+                    "column_start": 9,
+                    "column_end": 14,
+                    "is_primary": true,
+                    "text": [{ "text": "const a \"open", "highlight_start": 9, "highlight_end": 14 }],
+                    "label": "missing closing `\"`",
+                    "suggested_replacement": null,
+                    "suggestion_applicability": null,
+                    "expansion": null,
+                }],
+                "children": [
+                    {
+                        "message": "strings may span several lines, so the closing quote can be far away",
+                        "code": null,
+                        "level": "note",
+                        "spans": [],
+                        "children": [],
+                        "rendered": null,
+                    },
+                    {
+                        "message": "close the string",
+                        "code": null,
+                        "level": "help",
+                        "spans": [{
+                            "file_name": "a.osta",
+                            "byte_start": 22,
+                            "byte_end": 22,
+                            "line_start": 2,
+                            "line_end": 2,
+                            "column_start": 14,
+                            "column_end": 14,
+                            "is_primary": true,
+                            "text": [{ "text": "const a \"open", "highlight_start": 14, "highlight_end": 14 }],
+                            "label": null,
+                            "suggested_replacement": "\"",
+                            "suggestion_applicability": "Unspecified",
+                            "expansion": null,
+                        }],
+                        "children": [],
+                        "rendered": null,
+                    },
+                ],
+                "rendered": null,
+            })
+        );
+    }
+
+    #[test]
+    fn multiline_span_text() {
+        let index = LineIndex::new("const é\r\nconst a");
+        let diagnostic = Diagnostic::error("spans lines").with_label(Span::new(6, 16), "here");
+        let json: Value =
+            serde_json::from_str(&JsonEmitter::new().to_json(&diagnostic, &index, "a.osta"))
+                .unwrap();
+        assert_eq!(
+            json["spans"][0]["text"],
+            json!([
+                { "text": "const é", "highlight_start": 7, "highlight_end": 8 },
+                { "text": "const a", "highlight_start": 1, "highlight_end": 7 },
+            ])
+        );
+    }
+
+    #[test]
+    fn one_line_per_diagnostic() {
+        let index = LineIndex::new("a\nb");
+        let mut out = Vec::new();
+        let emitter = JsonEmitter::new();
+        emitter
+            .emit(&mut out, &Diagnostic::error("first"), &index, "a.osta")
+            .unwrap();
+        emitter
+            .emit(&mut out, &Diagnostic::warning("second"), &index, "a.osta")
+            .unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["level"], "warning");
+        assert_eq!(lines[1]["code"], Value::Null);
+        assert_eq!(lines[0]["rendered"], "error: first\n");
+    }
+}
@@ -0,0 +1,280 @@
+use crate::diagnostic::Diagnostic;
+use osta_lexer::{Expansion, LexerError, Span, Spanned};
+
+/// Returns the stable code of a lexer error.
+///
+/// Codes are never reused or renumbered; new variants get the next free code. Codes are shared
+/// with [`crate::parser::error_code`], and the arms of both are kept sorted by code.
+pub fn error_code(error: &LexerError) -> &'static str {
+    match error {
+        LexerError::UnknownToken => "E0001",
+        LexerError::InvalidInteger(_) => "E0002",
+        LexerError::UnterminatedBlockComment => "E0003",
+        LexerError::UnknownOperator => "E0004",
+        LexerError::InvalidEscape { .. } => "E0005",
+        LexerError::InvalidUnicodeEscape { .. } => "E0006",
+        LexerError::NonAsciiByte { .. } => "E0007",
+        LexerError::InvalidCharLiteral => "E0008",
+        LexerError::LiteralOutOfRange { .. } => "E0009",
+        LexerError::UnexpectedChar { .. } => "E0010",
+        LexerError::UnterminatedString { .. } => "E0011",
+        LexerError::UnterminatedChar { .. } => "E0012",
+        LexerError::UnterminatedRawString { .. } => "E0013",
+        LexerError::UnclosedDelimiter { .. } => "E0014",
+        LexerError::UnexpectedClosingDelimiter { .. } => "E0015",
+        LexerError::MismatchedDelimiter { .. } => "E0016",
+        LexerError::InvalidMacroDefinition { .. } => "E0017",
+        LexerError::MissingMacroArguments { .. } => "E0018",
+        LexerError::NoMatchingMacroRule { .. } => "E0019",
+        LexerError::MacroRepetitionMismatch { .. } => "E0020",
+        LexerError::MacroRecursionLimit { .. } => "E0021",
+        LexerError::InvalidDirective { .. } => "E0022",
+        LexerError::UnmatchedDirective { .. } => "E0023",
+        LexerError::UnterminatedConditional => "E0024",
+        LexerError::IncludeFailed { .. } => "E0025",
+        LexerError::DirectiveError { .. } => "E0026",
+        LexerError::ReservedKeyword { .. } => "E0030",
+        LexerError::UnknownEdition(_) => "E0031",
+        LexerError::UnsupportedFloatWidth { .. } => "E0034",
+    }
+}
+
+impl From<Spanned<LexerError>> for Diagnostic {
+    fn from(Spanned { value: error, span }: Spanned<LexerError>) -> Self {
+        let diagnostic = Diagnostic::error(error.to_string()).with_code(error_code(&error));
+        match error {
+            LexerError::UnknownToken => diagnostic.with_label(span, "not a valid token"),
+            LexerError::InvalidInteger(e) => diagnostic
+                .with_label(span, "invalid integer")
+                .with_note(e.to_string()),
+            LexerError::UnterminatedBlockComment => diagnostic
+                .with_label(Span::new(span.start, span.start + 2), "comment starts here")
+                .with_note("block comments nest, so every `/*` needs its own `*/`")
+                .with_suggestion(Span::new(span.end, span.end), "*/", "close the comment"),
+            LexerError::ReservedKeyword { keyword } => diagnostic
+                .with_label(span, "reserved for future use")
+                .with_suggestion(
+                    span,
+                    format!("r#{keyword}"),
+                    "use a raw identifier to name something with it",
+                ),
+            LexerError::UnknownOperator => diagnostic
+                .with_label(span, "no operator is defined with this spelling")
+                .with_help("separate the operators with spaces if this is more than one"),
+            LexerError::InvalidEscape { span: escape } => diagnostic
+                .with_label(escape, "unknown escape")
+                .with_label(span, "in this literal")
+                .with_help("use `\\\\` to write a literal backslash"),
+            LexerError::InvalidUnicodeEscape { span: escape } => diagnostic
+                .with_label(escape, "invalid unicode escape")
+                .with_label(span, "in this literal")
+                .with_help("unicode escapes look like `\\u{1F600}` and name a valid code point"),
+            LexerError::NonAsciiByte { span: byte } => diagnostic
+                .with_label(byte, "not ASCII")
+                .with_label(span, "in this byte literal")
+                .with_help("use a `\\x` escape to write bytes above `\\x7F`"),
+            LexerError::InvalidCharLiteral => diagnostic
+                .with_label(span, "character literal")
+                .with_help("use a string literal for more than one character"),
+            LexerError::LiteralOutOfRange { suffix } => diagnostic.with_label(
+                span,
+                match suffix {
+                    Some(suffix) => format!("does not fit in `{suffix}`"),
+                    None => "does not fit in the default float type, `f64`".to_string(),
+                },
+            ),
+            LexerError::UnsupportedFloatWidth { width } => diagnostic
+                .with_label(span, format!("`f{width}` is not a float type"))
+                .with_help("floats are `f16`, `f32` or `f64`"),
+            LexerError::UnexpectedChar { ch, span } => {
+                diagnostic.with_label(span, format!("unexpected `{}`", ch.escape_debug()))
+            }
+            LexerError::UnterminatedString { start } => diagnostic
+                .with_label(Span::new(start, span.end), "missing closing `\"`")
+                .with_note("strings may span several lines, so the closing quote can be far away")
+                .with_suggestion(Span::new(span.end, span.end), "\"", "close the string"),
+            LexerError::UnterminatedChar { start } => diagnostic
+                .with_label(Span::new(start, span.end), "missing closing `'`")
+                .with_suggestion(Span::new(span.end, span.end), "'", "close the literal"),
+            LexerError::UnterminatedRawString { hashes } => {
+                let terminator = format!("\"{}", "#".repeat(hashes));
+                diagnostic
+                    .with_label(
+                        Span::new(span.start, span.start + hashes + 2),
+                        "raw string starts here",
+                    )
+                    .with_help(format!("close it with `{terminator}`"))
+                    .with_suggestion(
+                        Span::new(span.end, span.end),
+                        terminator,
+                        "close the string",
+                    )
+            }
+            LexerError::UnclosedDelimiter { delimiter, open } => {
+                diagnostic.with_label(open, format!("`{}` is never closed", delimiter.open()))
+            }
+            LexerError::UnexpectedClosingDelimiter { delimiter } => {
+                diagnostic.with_label(span, format!("no `{}` to close", delimiter.open()))
+            }
+            LexerError::MismatchedDelimiter {
+                expected,
+                found,
+                open,
+                close,
+            } => diagnostic
+                .with_label(close, format!("expected `{}`", expected.close()))
+                .with_label(open, format!("`{}` opened here", expected.open()))
+                .with_suggestion(
+                    close,
+                    expected.close().to_string(),
+                    format!(
+                        "close the `{}` instead of `{}`",
+                        expected.open(),
+                        found.open()
+                    ),
+                ),
+            LexerError::InvalidMacroDefinition { reason } => diagnostic.with_label(span, reason),
+            LexerError::MissingMacroArguments { name } => diagnostic
+                .with_label(span, "expected arguments in delimiters")
+                .with_suggestion(
+                    Span::new(span.end, span.end),
+                    "()",
+                    format!("invoke `@{name}` with no arguments"),
+                ),
+            LexerError::NoMatchingMacroRule { .. } => {
+                diagnostic.with_label(span, "no rule matches these arguments")
+            }
+            LexerError::MacroRepetitionMismatch { .. } => diagnostic
+                .with_label(span, "in this invocation")
+                .with_note("metavariables repeated together must match the same number of times"),
+            LexerError::MacroRecursionLimit { name } => diagnostic
+                .with_label(span, "expanded too deeply")
+                .with_note(format!(
+                    "`@{name}` might expand to an invocation of itself that never ends"
+                )),
+            LexerError::InvalidDirective { reason } => diagnostic.with_label(span, reason),
+            LexerError::UnmatchedDirective { name } => diagnostic
+                .with_label(span, format!("no `$if` to {name}"))
+                .with_help("remove this directive, or open a conditional before it"),
+            LexerError::UnterminatedConditional => diagnostic
+                .with_label(span, "conditional starts here")
+                .with_note("every `$if` needs an `$endif` in the same file"),
+            LexerError::IncludeFailed { .. } => diagnostic.with_label(span, "included here"),
+            LexerError::DirectiveError { .. } => {
+                diagnostic.with_label(span, "reported by this directive")
+            }
+            LexerError::UnknownEdition(_) => diagnostic.with_label(span, "not a known edition"),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Adds a label for the call and definition site of each expansion in `backtrace`, innermost
+    /// first as returned by [`osta_lexer::Lexer::backtrace`].
+    pub fn with_backtrace<'a>(self, backtrace: impl IntoIterator<Item = &'a Expansion>) -> Self {
+        backtrace.into_iter().fold(self, |diagnostic, expansion| {
+            diagnostic
+                .with_label(
+                    expansion.call_site,
+                    format!("in this expansion of `@{}`", expansion.name),
+                )
+                .with_label(
+                    expansion.def_site,
+                    format!("`@{}` defined here", expansion.name),
+                )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use osta_lexer::{Lexer, Span, TokenTree};
+
+    fn diagnose(source: &str) -> Diagnostic {
+        let error = Lexer::new(source).find_map(Result::err).unwrap();
+        error.into()
+    }
+
+    #[test]
+    fn codes() {
+        let diagnostic = diagnose("a § b");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.code, Some("E0010"));
+        assert_eq!(diagnostic.message, "unexpected character `§`");
+        assert_eq!(diagnostic.span(), Some(Span::new(2, 4)));
+
+        assert_eq!(diagnose("/* open").code, Some("E0003"));
+        assert_eq!(diagnose("1i99999999999999999999").code, Some("E0002"));
+
+        let diagnostic = diagnose("0x1p1024");
+        assert_eq!(diagnostic.code, Some("E0009"));
+        assert_eq!(
+            diagnostic.message,
+            "literal out of range for the default float type"
+        );
+        assert_eq!(diagnose("256u8").message, "literal out of range for `u8`");
+
+        let diagnostic = diagnose("let match = 1");
+        assert_eq!(diagnostic.code, Some("E0030"));
+        assert_eq!(diagnostic.message, "`match` is a reserved keyword");
+        assert_eq!(diagnostic.suggestions[0].replacement, "r#match");
+    }
+
+    #[test]
+    fn labels() {
+        let diagnostic = diagnose(r#""a \q b""#);
+        assert_eq!(
+            diagnostic.labels,
+            vec![
+                (Span::new(3, 5), "unknown escape".to_string()),
+                (Span::new(0, 8), "in this literal".to_string()),
+            ]
+        );
+
+        let diagnostic = diagnose(r##"r##"open"##);
+        assert_eq!(diagnostic.span(), Some(Span::new(0, 4)));
+        assert_eq!(diagnostic.help.as_deref(), Some("close it with `\"##`"));
+        assert_eq!(diagnostic.suggestions[0].span, Span::new(8, 8));
+        assert_eq!(diagnostic.suggestions[0].replacement, "\"##");
+    }
+
+    #[test]
+    fn delimiters() {
+        let (_, errors) = TokenTree::parse_all(&mut Lexer::new("(a]) }"));
+        let diagnostics: Vec<Diagnostic> = errors.into_iter().map(Diagnostic::from).collect();
+        assert_eq!(diagnostics[0].code, Some("E0015"));
+        assert_eq!(diagnostics[0].span(), Some(Span::new(2, 3)));
+        assert_eq!(diagnostics[1].code, Some("E0015"));
+        assert_eq!(diagnostics[1].message, "unexpected closing delimiter `}`");
+
+        let (_, errors) = TokenTree::parse_all(&mut Lexer::new("{ (a }"));
+        let diagnostic = Diagnostic::from(errors[0].clone());
+        assert_eq!(diagnostic.code, Some("E0016"));
+        assert_eq!(
+            diagnostic.labels,
+            vec![
+                (Span::new(5, 6), "expected `)`".to_string()),
+                (Span::new(2, 3), "`(` opened here".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn backtraces() {
+        let source = "@macro_rules m { () => { x }; }\n@m()";
+        let mut lexer = Lexer::new(source);
+        let token = lexer.next().unwrap().unwrap();
+        let diagnostic = Diagnostic::error("unresolved name")
+            .with_label(token.span, "not found")
+            .with_backtrace(lexer.backtrace(token.expansion));
+        assert_eq!(
+            diagnostic.labels,
+            vec![
+                (Span::new(25, 26), "not found".to_string()),
+                (Span::new(32, 36), "in this expansion of `@m`".to_string()),
+                (Span::new(0, 31), "`@m` defined here".to_string()),
+            ]
+        );
+    }
+}
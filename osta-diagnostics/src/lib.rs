@@ -0,0 +1,9 @@
+pub mod diagnostic;
+pub mod json;
+pub mod lexer;
+pub mod parser;
+pub mod render;
+
+pub use diagnostic::{Diagnostic, Severity, Suggestion};
+pub use json::JsonEmitter;
+pub use render::Renderer;
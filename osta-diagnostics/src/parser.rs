@@ -0,0 +1,72 @@
+use crate::diagnostic::Diagnostic;
+use crate::lexer;
+use osta_lexer::Spanned;
+use osta_parser::ParseError;
+
+/// Returns the stable code of a parse error, see [`lexer::error_code`].
+pub fn error_code(error: &ParseError) -> &'static str {
+    match error {
+        ParseError::Lexer(error) => lexer::error_code(error),
+        ParseError::Expected { .. } => "E0027",
+        ParseError::DuplicateModifier(_) => "E0028",
+        ParseError::InvalidModifier { .. } => "E0029",
+        ParseError::NonAssociative { .. } => "E0032",
+        ParseError::ExpectedOperatorPart { .. } => "E0033",
+    }
+}
+
+impl From<Spanned<ParseError>> for Diagnostic {
+    fn from(Spanned { value: error, span }: Spanned<ParseError>) -> Self {
+        let diagnostic = Diagnostic::error(error.to_string()).with_code(error_code(&error));
+        match error {
+            ParseError::Lexer(error) => Spanned::new(error, span).into(),
+            ParseError::Expected { expected, .. } => {
+                diagnostic.with_label(span, format!("expected {expected}"))
+            }
+            ParseError::DuplicateModifier(modifier) => diagnostic
+                .with_label(span, "already given")
+                .with_suggestion(
+                    span,
+                    "",
+                    format!("remove the second `{}`", modifier.as_str()),
+                ),
+            ParseError::InvalidModifier { .. } => diagnostic.with_label(span, "not allowed here"),
+            ParseError::NonAssociative { .. } => diagnostic
+                .with_label(span, "chained here")
+                .with_help("use parentheses to group the operations"),
+            ParseError::ExpectedOperatorPart { part, .. } => {
+                diagnostic.with_label(span, format!("expected `{part}`"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use osta_lexer::Span;
+
+    fn diagnose(source: &str) -> Vec<Diagnostic> {
+        let (_, errors) = osta_parser::parse(source);
+        errors.into_iter().map(Diagnostic::from).collect()
+    }
+
+    #[test]
+    fn codes() {
+        let diagnostics = diagnose("const const X: § = 1;");
+        assert_eq!(diagnostics[0].code, Some("E0028"));
+        assert_eq!(diagnostics[0].suggestions[0].span, Span::new(6, 11));
+        assert_eq!(diagnostics[1].code, Some("E0010"));
+        assert_eq!(diagnostics[1].message, "unexpected character `§`");
+        assert_eq!(diagnostics[2].code, Some("E0027"));
+        assert_eq!(diagnostics[2].message, "expected a type, found `=`");
+        assert_eq!(
+            diagnostics[2].labels,
+            vec![(Span::new(18, 19), "expected a type".to_string())]
+        );
+
+        let diagnostics = diagnose("const X = a == b != c;");
+        assert_eq!(diagnostics[0].code, Some("E0032"));
+        assert_eq!(diagnostics[0].labels[0].0, Span::new(17, 19));
+    }
+}
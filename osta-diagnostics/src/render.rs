@@ -0,0 +1,277 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use osta_lexer::{Encoding, LineIndex, Span};
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+
+const TAB_WIDTH: usize = 4;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Renders diagnostics as source excerpts with underlined labels, in the style of `rustc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    pub fn new(color: bool) -> Self {
+        Self { color }
+    }
+
+    pub fn plain() -> Self {
+        Self::new(false)
+    }
+
+    /// Uses colors only if `stream` is a terminal and `NO_COLOR` is not set.
+    pub fn for_stream(stream: &impl IsTerminal) -> Self {
+        Self::new(stream.is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()))
+    }
+
+    /// Renders `diagnostic` against the source in `index`, which was read from `path`.
+    pub fn render(&self, diagnostic: &Diagnostic, index: &LineIndex, path: &str) -> String {
+        let mut out = String::new();
+        let severity = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => GREEN,
+        };
+
+        let _ = write!(out, "{}{}", self.paint(severity), diagnostic.severity);
+        if let Some(code) = diagnostic.code {
+            let _ = write!(out, "[{code}]");
+        }
+        let _ = writeln!(
+            out,
+            "{}: {}{}",
+            self.paint(BOLD),
+            diagnostic.message,
+            self.paint(RESET)
+        );
+
+        let mut labels: Vec<_> = diagnostic
+            .labels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (span, message))| {
+                let start = index.line_col(span.start, Encoding::Utf8)?;
+                Some((start.line, i == 0, *span, message.as_str()))
+            })
+            .collect();
+        let gutter = labels
+            .iter()
+            .map(|&(line, ..)| (line + 1).to_string().len())
+            .max()
+            .unwrap_or(0);
+        let blank = " ".repeat(gutter);
+        let (blue, reset) = (self.paint(BLUE), self.paint(RESET));
+
+        if let Some(span) = diagnostic.span()
+            && let Some(pos) = index.line_col(span.start, Encoding::Utf32)
+        {
+            let _ = writeln!(
+                out,
+                "{blank}{blue}-->{reset} {path}:{}:{}",
+                pos.line + 1,
+                pos.col + 1
+            );
+        }
+
+        if !labels.is_empty() {
+            let _ = writeln!(out, "{blank} {blue}|{reset}");
+        }
+        // Lines in source order, with the labels on each line ordered by column
+        labels.sort_by_key(|&(line, _, span, _)| (line, span.start));
+        let mut remaining = labels.as_slice();
+        while let Some(&(line, ..)) = remaining.first() {
+            let count = remaining.iter().take_while(|label| label.0 == line).count();
+            let (current, rest) = remaining.split_at(count);
+            self.render_line(&mut out, index, line, current, gutter, severity);
+            remaining = rest;
+        }
+
+        let has_footer = !diagnostic.notes.is_empty()
+            || diagnostic.help.is_some()
+            || !diagnostic.suggestions.is_empty();
+        if !labels.is_empty() && has_footer {
+            let _ = writeln!(out, "{blank} {blue}|{reset}");
+        }
+        let bold = self.paint(BOLD);
+        for note in &diagnostic.notes {
+            let _ = writeln!(out, "{blank} {blue}={reset} {bold}note{reset}: {note}");
+        }
+        if let Some(help) = &diagnostic.help {
+            let _ = writeln!(out, "{blank} {blue}={reset} {bold}help{reset}: {help}");
+        }
+        for suggestion in &diagnostic.suggestions {
+            let _ = writeln!(
+                out,
+                "{blank} {blue}={reset} {bold}help{reset}: {}: `{}`",
+                suggestion.message, suggestion.replacement
+            );
+        }
+        out
+    }
+
+    /// Writes the rendered `diagnostic` to `writer`, followed by a blank line.
+    pub fn emit(
+        &self,
+        writer: &mut impl Write,
+        diagnostic: &Diagnostic,
+        index: &LineIndex,
+        path: &str,
+    ) -> io::Result<()> {
+        writeln!(writer, "{}", self.render(diagnostic, index, path))
+    }
+
+    fn render_line(
+        &self,
+        out: &mut String,
+        index: &LineIndex,
+        line: usize,
+        labels: &[(usize, bool, Span, &str)],
+        gutter: usize,
+        severity: &'static str,
+    ) {
+        let (blue, reset) = (self.paint(BLUE), self.paint(RESET));
+        let Some(line_span) = index.line_span(line) else {
+            return;
+        };
+        let text = &index.source()[line_span.range()];
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        let _ = writeln!(
+            out,
+            "{blue}{:>gutter$} |{reset} {}",
+            line + 1,
+            text.replace('\t', &" ".repeat(TAB_WIDTH))
+        );
+
+        for &(_, primary, span, message) in labels {
+            // Labels running past the end of the line, or starting on its `\r`, are cut off there
+            let start = (span.start - line_span.start).min(text.len());
+            let end = (span.end.min(line_span.start + text.len()) - line_span.start).max(start);
+            let pad = display_width(&text[..start]);
+            let width = display_width(&text[start..end]).max(1);
+            let (color, mark) = match primary {
+                true => (severity, "^"),
+                false => (BLUE, "-"),
+            };
+            let _ = write!(
+                out,
+                "{:gutter$} {blue}|{reset} {}{}{}",
+                "",
+                " ".repeat(pad),
+                self.paint(color),
+                mark.repeat(width)
+            );
+            if message.is_empty() {
+                let _ = writeln!(out, "{reset}");
+            } else {
+                let _ = writeln!(out, " {message}{reset}");
+            }
+        }
+    }
+
+    fn paint(&self, code: &'static str) -> &'static str {
+        if self.color { code } else { "" }
+    }
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Renderer;
+    use crate::diagnostic::Diagnostic;
+    use osta_lexer::{LineIndex, Span};
+
+    const SOURCE: &str = "const a\n\tstatic é § b\nconst c";
+
+    #[test]
+    fn plain() {
+        let index = LineIndex::new(SOURCE);
+        let section = SOURCE.find('§').unwrap();
+        let diagnostic = Diagnostic::error("unexpected character `§`")
+            .with_code("E0010")
+            .with_label(Span::new(section, section + 2), "unexpected `§`")
+            .with_label(Span::new(0, 5), "")
+            .with_note("a note")
+            .with_help("some help");
+
+        assert_eq!(
+            Renderer::plain().render(&diagnostic, &index, "main.osta"),
+            "\
+error[E0010]: unexpected character `§`
+ --> main.osta:2:11
+  |
+1 | const a
+  | -----
+2 |     static é § b
+  |              ^ unexpected `§`
+  |
+  = note: a note
+  = help: some help
+"
+        );
+    }
+
+    #[test]
+    fn multiline_label() {
+        let index = LineIndex::new(SOURCE);
+        let diagnostic = Diagnostic::warning("spans lines").with_label(Span::new(4, 12), "here");
+        assert_eq!(
+            Renderer::plain().render(&diagnostic, &index, "main.osta"),
+            "\
+warning: spans lines
+ --> main.osta:1:5
+  |
+1 | const a
+  |     ^^^ here
+"
+        );
+    }
+
+    #[test]
+    fn crlf_line_end() {
+        let index = LineIndex::new("const a\r\nb");
+        let diagnostic =
+            Diagnostic::error("unexpected end of line").with_label(Span::new(8, 9), "here");
+        assert_eq!(
+            Renderer::plain().render(&diagnostic, &index, "main.osta"),
+            "\
+error: unexpected end of line
+ --> main.osta:1:9
+  |
+1 | const a
+  |        ^ here
+"
+        );
+    }
+
+    #[test]
+    fn colored() {
+        let index = LineIndex::new(SOURCE);
+        let diagnostic = Diagnostic::error("oops").with_label(Span::new(0, 5), "here");
+        let rendered = Renderer::new(true).render(&diagnostic, &index, "main.osta");
+        assert!(rendered.starts_with("\x1b[1;31merror\x1b[1m: oops\x1b[0m\n"));
+        assert!(rendered.contains("\x1b[1;31m^^^^^ here\x1b[0m"));
+    }
+
+    #[test]
+    fn no_location() {
+        let index = LineIndex::new(SOURCE);
+        let diagnostic = Diagnostic::error("no input files").with_help("pass a file");
+        assert_eq!(
+            Renderer::plain().render(&diagnostic, &index, "main.osta"),
+            "error: no input files\n = help: pass a file\n"
+        );
+    }
+}
@@ -0,0 +1,888 @@
+//! Compile-time directives, processed between the lexer and the parser.
+//!
+//! A [`Preprocessor`] reads tokens from a [`Lexer`] and handles the directives below, passing
+//! every other token through:
+//!
+//! - `$if (condition)`, `$else` and `$endif` keep the tokens of the first branch whose condition
+//!   holds for the [`Config`] and drop the others. Conditions combine `key`, which holds if the
+//!   key is set, and `key == value` or `key != value` with `!`, `&&`, `||` and parentheses.
+//!   Conditions are lexed again from the source with only these operators, so they don't depend
+//!   on the operator table of the lexer.
+//! - `$include("path")` is replaced by the tokens of the file, read with a [`SourceLoader`].
+//! - `$error("message")` reports an error, and `$warning("message")` a warning.
+//! - `$edition(2024)` sets the [`Edition`] the rest of the file is lexed with. It must come
+//!   before any other token of the file, and files without it use the edition of the lexer the
+//!   preprocessor was created with.
+//!
+//! Directives in dropped branches are ignored, except for tracking nested `$if`s, and macros in
+//! them are neither defined nor expanded. Since macros are expanded by the lexer, macros in kept
+//! branches may expand to directives.
+
+use crate::keyword::Edition;
+use crate::lexer::{Lexer, LexerError, TokenResult};
+use crate::operator::{Associativity, OperatorDef, OperatorTable};
+use crate::span::{Span, Spanned};
+use crate::token::{Token, TokenKind};
+use crate::token_tree::{Delimiter, Group, TokenTree, parse_group};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+
+/// Identifies the source file a token was read from, [`FileId::ROOT`] being the file the
+/// [`Preprocessor`] was created for.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+impl FileId {
+    pub const ROOT: FileId = FileId(0);
+}
+
+/// The key/value settings conditions are evaluated against.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `key` to `value`, returning the previous value.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.values.insert(key.into(), value.into())
+    }
+
+    /// Returns the config with `key` set to `value`.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.set(key, value);
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Config {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let values = iter
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        Self { values }
+    }
+}
+
+/// Reads the files named by `$include` directives.
+///
+/// Sources must outlive the tokens read from them, so loaders typically own or leak them.
+pub trait SourceLoader<'src> {
+    fn load(&mut self, path: &str) -> io::Result<&'src str>;
+}
+
+/// Loads sources by their exact path, which is useful for tests and in-memory sources.
+impl<'src> SourceLoader<'src> for HashMap<String, &'src str> {
+    fn load(&mut self, path: &str) -> io::Result<&'src str> {
+        self.get(path)
+            .copied()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file not found"))
+    }
+}
+
+/// A file read by a [`Preprocessor`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile<'src> {
+    /// The path the file was included with, or an empty path for the root file.
+    pub path: String,
+    pub source: &'src str,
+    /// The file and span of the `$include` directive that read this file.
+    pub included_at: Option<(FileId, Span)>,
+}
+
+/// Handles compile-time directives in the token stream of a [`Lexer`], see the [module
+/// documentation](self).
+pub struct Preprocessor<'src> {
+    /// The files being read, the current one last.
+    frames: Vec<Frame<'src>>,
+    files: Vec<SourceFile<'src>>,
+    config: Config,
+    loader: Box<dyn SourceLoader<'src> + 'src>,
+    warnings: Vec<(FileId, Spanned<String>)>,
+    /// Errors found along with another one, reported after it, with the file of each.
+    errors: VecDeque<(FileId, Spanned<LexerError>)>,
+    file: FileId,
+    /// The edition of files without an `$edition` directive.
+    edition: Edition,
+}
+
+struct Frame<'src> {
+    file: FileId,
+    lexer: Lexer<'src>,
+    conditionals: Vec<Conditional>,
+    /// Whether a token has been read from the file, after which `$edition` is not allowed.
+    started: bool,
+}
+
+struct Conditional {
+    /// The span of the `$if`.
+    span: Span,
+    /// Whether a branch has been kept.
+    taken: bool,
+    has_else: bool,
+}
+
+impl<'src> Preprocessor<'src> {
+    /// Creates a preprocessor for `lexer` that can't include files, see
+    /// [`Preprocessor::with_loader`].
+    pub fn new(lexer: Lexer<'src>, config: Config) -> Self {
+        let root = SourceFile {
+            path: String::new(),
+            source: lexer.source(),
+            included_at: None,
+        };
+        let edition = lexer.edition();
+        Self {
+            frames: vec![Frame {
+                file: FileId::ROOT,
+                lexer,
+                conditionals: Vec::new(),
+                started: false,
+            }],
+            files: vec![root],
+            config,
+            loader: Box::new(HashMap::new()),
+            warnings: Vec::new(),
+            errors: VecDeque::new(),
+            file: FileId::ROOT,
+            edition,
+        }
+    }
+
+    /// Sets the loader used to read the files named by `$include`.
+    pub fn with_loader(mut self, loader: impl SourceLoader<'src> + 'src) -> Self {
+        self.loader = Box::new(loader);
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the file the last token or error was read from.
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    pub fn source_file(&self, id: FileId) -> Option<&SourceFile<'src>> {
+        self.files.get(id.0 as usize)
+    }
+
+    /// Returns the warnings reported by `$warning` since the last call, with the file of each.
+    pub fn take_warnings(&mut self) -> Vec<(FileId, Spanned<String>)> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Handles the directive `token`, `started` telling whether tokens of the file came before.
+    fn directive(&mut self, token: Token<'src>, started: bool) -> Option<TokenResult<'src>> {
+        let error = |error, span| Some(Err(Spanned::new(error, span)));
+        let frame = self.frames.last_mut().unwrap();
+
+        match &token.slice[1..] {
+            "if" => {
+                // Unknown operators are reported when lexing the condition again
+                let taken = read_group(&mut frame.lexer, &token).and_then(|(group, errors)| {
+                    match errors
+                        .into_iter()
+                        .find(|e| e.value != LexerError::UnknownOperator)
+                    {
+                        Some(error) => Err(error),
+                        None => {
+                            let source = self.files[group.file.0 as usize].source;
+                            self.config.eval_condition(&group, source)
+                        }
+                    }
+                });
+                let (taken, result) = match taken {
+                    Ok(taken) => (taken, None),
+                    Err(e) => (false, Some(Err(e))),
+                };
+                frame.conditionals.push(Conditional {
+                    span: token.span,
+                    taken,
+                    has_else: false,
+                });
+                if !taken && let Some(error) = frame.skip() {
+                    // An error in the condition comes first
+                    match result {
+                        Some(_) => self.errors.push_back((frame.file, error)),
+                        None => return Some(Err(error)),
+                    }
+                }
+                result
+            }
+            "else" => match frame.conditionals.last_mut() {
+                None => error(unmatched("else"), token.span),
+                Some(conditional) if conditional.has_else => error(
+                    LexerError::InvalidDirective {
+                        reason: "`$else` after `$else`",
+                    },
+                    token.span,
+                ),
+                Some(conditional) => {
+                    conditional.has_else = true;
+                    frame.skip().map(Err)
+                }
+            },
+            "endif" => match frame.conditionals.pop() {
+                None => error(unmatched("endif"), token.span),
+                Some(_) => None,
+            },
+            "edition" => {
+                let group = match read_args(&mut frame.lexer, &token) {
+                    Ok(group) => group,
+                    Err(e) => return Some(Err(e)),
+                };
+                let span = token.span.to(group.close);
+                if started {
+                    let reason = "`$edition` must come before any other token of the file";
+                    return error(LexerError::InvalidDirective { reason }, span);
+                }
+                match edition_arg(group) {
+                    Ok(edition) => {
+                        frame.lexer.set_edition(edition);
+                        None
+                    }
+                    Err(e) => Some(Err(e)),
+                }
+            }
+            "include" | "error" | "warning" => {
+                let group = match read_args(&mut frame.lexer, &token) {
+                    Ok(group) => group,
+                    Err(e) => return Some(Err(e)),
+                };
+                let span = token.span.to(group.close);
+                let arg = match string_arg(group) {
+                    Ok(arg) => arg,
+                    Err(e) => return Some(Err(e)),
+                };
+                match token.slice {
+                    "$include" => self.include(arg, span),
+                    "$error" => error(LexerError::DirectiveError { message: arg }, span),
+                    _ => {
+                        self.warnings.push((token.file, Spanned::new(arg, span)));
+                        None
+                    }
+                }
+            }
+            _ => Some(Ok(token)),
+        }
+    }
+
+    fn include(&mut self, path: String, span: Span) -> Option<TokenResult<'src>> {
+        let failed = |reason: String, path| {
+            Some(Err(Spanned::new(
+                LexerError::IncludeFailed { path, reason },
+                span,
+            )))
+        };
+        if self
+            .frames
+            .iter()
+            .any(|frame| self.files[frame.file.0 as usize].path == path)
+        {
+            return failed(format!("`{path}` is already being included"), path);
+        }
+        let source = match self.loader.load(&path) {
+            Ok(source) => source,
+            Err(e) => return failed(e.to_string(), path),
+        };
+
+        let parent = self.frames.last_mut().unwrap();
+        let file = FileId(self.files.len() as u32);
+        let mut lexer = Lexer::with_edition(source, self.edition);
+        lexer.set_file(file);
+        lexer.swap_state(&mut parent.lexer);
+        self.files.push(SourceFile {
+            path,
+            source,
+            included_at: Some((parent.file, span)),
+        });
+        self.frames.push(Frame {
+            file,
+            lexer,
+            conditionals: Vec::new(),
+            started: false,
+        });
+        None
+    }
+}
+
+impl<'src> Frame<'src> {
+    /// Drops tokens up to the `$else` or `$endif` of the innermost conditional, returning the
+    /// first error in the directives it stops at. Macros are not expanded, so the ones defined
+    /// in dropped tokens are not defined.
+    fn skip(&mut self) -> Option<Spanned<LexerError>> {
+        let mut depth = 0;
+        while let Some(result) = self.lexer.bump_unexpanded() {
+            let Ok(token) = result else {
+                continue;
+            };
+            if token.kind != TokenKind::DirectiveIdentifier {
+                continue;
+            }
+            match (&token.slice[1..], depth) {
+                ("if", _) => depth += 1,
+                ("endif", 0) => {
+                    self.conditionals.pop();
+                    return None;
+                }
+                ("endif", _) => depth -= 1,
+                ("else", 0) => {
+                    let conditional = self.conditionals.last_mut().unwrap();
+                    if conditional.has_else {
+                        let reason = "`$else` after `$else`";
+                        let error = LexerError::InvalidDirective { reason };
+                        return Some(Spanned::new(error, token.span));
+                    }
+                    conditional.has_else = true;
+                    if !conditional.taken {
+                        conditional.taken = true;
+                        return None;
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+impl<'src> Iterator for Preprocessor<'src> {
+    type Item = TokenResult<'src>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((file, error)) = self.errors.pop_front() {
+            self.file = file;
+            return Some(Err(error));
+        }
+        loop {
+            let frame = self.frames.last_mut()?;
+            self.file = frame.file;
+            let Some(result) = frame.lexer.next() else {
+                if let Some(conditional) = frame.conditionals.pop() {
+                    let error = LexerError::UnterminatedConditional;
+                    return Some(Err(Spanned::new(error, conditional.span)));
+                }
+                let mut frame = self.frames.pop().unwrap();
+                if let Some(parent) = self.frames.last_mut() {
+                    parent.lexer.swap_state(&mut frame.lexer);
+                }
+                continue;
+            };
+
+            let started = std::mem::replace(&mut frame.started, true);
+            match result {
+                Ok(token) if token.kind == TokenKind::DirectiveIdentifier => {
+                    if let Some(result) = self.directive(token, started) {
+                        return Some(result);
+                    }
+                }
+                result => return Some(result),
+            }
+        }
+    }
+}
+
+/// Returns the edition set by the `$edition` directive `source` starts with, if any, for tools
+/// that lex a file without a [`Preprocessor`].
+pub fn source_edition(source: &str) -> Option<Result<Edition, Spanned<LexerError>>> {
+    let mut lexer = Lexer::new(source);
+    let directive = match lexer.next()? {
+        Ok(token) if token.slice == "$edition" => token,
+        _ => return None,
+    };
+    Some(read_args(&mut lexer, &directive).and_then(edition_arg))
+}
+
+fn unmatched(name: &'static str) -> LexerError {
+    LexerError::UnmatchedDirective { name }
+}
+
+/// Reads the parenthesized arguments of `directive`.
+fn read_args<'src>(
+    lexer: &mut Lexer<'src>,
+    directive: &Token<'src>,
+) -> Result<Group<'src>, Spanned<LexerError>> {
+    let (group, errors) = read_group(lexer, directive)?;
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(group),
+    }
+}
+
+/// Reads the group following `directive`, with the errors in it.
+fn read_group<'src>(
+    lexer: &mut Lexer<'src>,
+    directive: &Token<'src>,
+) -> Result<(Group<'src>, Vec<Spanned<LexerError>>), Spanned<LexerError>> {
+    if lexer.peek_kind(0) != Some(&TokenKind::LParen) {
+        let reason = "expected arguments in parentheses";
+        return Err(Spanned::new(
+            LexerError::InvalidDirective { reason },
+            directive.span,
+        ));
+    }
+    let open = lexer.next().unwrap().unwrap();
+    Ok(parse_group(open, || lexer.next()))
+}
+
+/// Returns the value of a group containing a single string literal.
+fn string_arg(group: Group) -> Result<String, Spanned<LexerError>> {
+    let invalid = Spanned::new(
+        LexerError::InvalidDirective {
+            reason: "expected a string literal",
+        },
+        group.span(),
+    );
+    match &group.trees[..] {
+        [TokenTree::Token(token)] => match token.string_value() {
+            Ok(value) => Ok(value.into_owned()),
+            Err(_) => Err(invalid),
+        },
+        _ => Err(invalid),
+    }
+}
+
+/// Returns the edition named by a group containing a single integer, like `(2024)`.
+fn edition_arg(group: Group) -> Result<Edition, Spanned<LexerError>> {
+    match &group.trees[..] {
+        [TokenTree::Token(token)] if token.kind == TokenKind::DecInt(None) => token
+            .slice
+            .parse()
+            .map_err(|e| Spanned::new(LexerError::UnknownEdition(e), token.span)),
+        _ => Err(Spanned::new(
+            LexerError::InvalidDirective {
+                reason: "expected an edition, like `2024`",
+            },
+            group.span(),
+        )),
+    }
+}
+
+type EvalResult = Result<bool, Spanned<LexerError>>;
+
+/// Returns a table of the operators conditions combine keys with.
+fn condition_operators() -> OperatorTable {
+    let mut table = OperatorTable::new();
+    table.define("!", OperatorDef::prefix(3)).unwrap();
+    table
+        .define("==", OperatorDef::infix(2, Associativity::None))
+        .unwrap();
+    table
+        .define("!=", OperatorDef::infix(2, Associativity::None))
+        .unwrap();
+    table
+        .define("&&", OperatorDef::infix(1, Associativity::Left))
+        .unwrap();
+    table
+        .define("||", OperatorDef::infix(0, Associativity::Left))
+        .unwrap();
+    table
+}
+
+/// The part of a condition left to evaluate, the keys, values and operators of a group.
+#[derive(Clone, Copy)]
+struct Condition<'a, 'src> {
+    trees: &'a [TokenTree<'src>],
+    span: Span,
+}
+
+impl<'a, 'src> Condition<'a, 'src> {
+    /// Returns the next tree, skipping comments.
+    fn peek(&self) -> Option<&'a TokenTree<'src>> {
+        self.trees.iter().find(|tree| !is_comment(tree))
+    }
+
+    fn next(&mut self) -> Option<&'a TokenTree<'src>> {
+        let index = self.trees.iter().position(|tree| !is_comment(tree))?;
+        let tree = &self.trees[index];
+        self.trees = &self.trees[index + 1..];
+        Some(tree)
+    }
+
+    fn eat_operator(&mut self, spelling: &str) -> bool {
+        match self.peek() {
+            Some(TokenTree::Token(token)) if is_operator(token) && token.slice == spelling => {
+                self.next();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn is_comment(tree: &TokenTree) -> bool {
+    matches!(tree, TokenTree::Token(token) if token.kind.is_trivia())
+}
+
+fn is_operator(token: &Token) -> bool {
+    matches!(token.kind, TokenKind::Operator(_))
+}
+
+impl Config {
+    /// Evaluates the condition in `group`, lexing it again from `source`, the source of its file,
+    /// with the [condition operators](condition_operators).
+    fn eval_condition(&self, group: &Group, source: &str) -> EvalResult {
+        let mut lexer = Lexer::with_operators(&source[..group.close.start], condition_operators());
+        lexer.set_file(group.file);
+        lexer.seek(group.open.end);
+        let (trees, errors) = TokenTree::parse_all(&mut lexer);
+        if let Some(error) = errors.into_iter().next() {
+            return Err(match error.value {
+                LexerError::UnknownOperator => {
+                    invalid_condition("expected `!`, `&&`, `||`, `==` or `!=`", error.span)
+                }
+                _ => error,
+            });
+        }
+        self.eval(&trees, group.span())
+    }
+
+    /// Evaluates the condition made of `trees`, the contents of the group at `span`.
+    fn eval(&self, trees: &[TokenTree], span: Span) -> EvalResult {
+        let mut condition = Condition { trees, span };
+        let value = self.eval_or(&mut condition)?;
+        match condition.peek() {
+            None => Ok(value),
+            Some(tree) => Err(invalid_condition("expected `&&` or `||`", tree.span())),
+        }
+    }
+
+    fn eval_or(&self, condition: &mut Condition) -> EvalResult {
+        let mut value = self.eval_and(condition)?;
+        while condition.eat_operator("||") {
+            value |= self.eval_and(condition)?;
+        }
+        Ok(value)
+    }
+
+    fn eval_and(&self, condition: &mut Condition) -> EvalResult {
+        let mut value = self.eval_not(condition)?;
+        while condition.eat_operator("&&") {
+            value &= self.eval_not(condition)?;
+        }
+        Ok(value)
+    }
+
+    fn eval_not(&self, condition: &mut Condition) -> EvalResult {
+        if condition.eat_operator("!") {
+            return Ok(!self.eval_not(condition)?);
+        }
+
+        let span = condition.span;
+        let Some(first) = condition.next() else {
+            return Err(invalid_condition("expected a condition", span));
+        };
+        let key = match first {
+            TokenTree::Group(group) if group.delimiter == Delimiter::Paren => {
+                return self.eval(&group.trees, group.span());
+            }
+            TokenTree::Token(token) if token.kind == TokenKind::Identifier => token.slice,
+            _ => return Err(invalid_condition("expected a config key", first.span())),
+        };
+
+        let equal = if condition.eat_operator("==") {
+            true
+        } else if condition.eat_operator("!=") {
+            false
+        } else {
+            return Ok(self.get(key).is_some());
+        };
+        let Some(TokenTree::Token(token)) = condition.next() else {
+            return Err(invalid_condition("expected a value to compare with", span));
+        };
+        let value = match token.kind {
+            TokenKind::String | TokenKind::RawString => token.string_value().ok(),
+            TokenKind::Identifier | TokenKind::DecInt(None) => Some(token.slice.into()),
+            _ => None,
+        };
+        let Some(value) = value else {
+            return Err(invalid_condition(
+                "expected a value to compare with",
+                token.span,
+            ));
+        };
+        Ok((self.get(key) == Some(&*value)) == equal)
+    }
+}
+
+fn invalid_condition(reason: &'static str, span: Span) -> Spanned<LexerError> {
+    Spanned::new(LexerError::InvalidDirective { reason }, span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, FileId, Preprocessor, source_edition};
+    use crate::keyword::{Edition, UnknownEdition};
+    use crate::lexer::{Lexer, LexerError};
+    use crate::operator::{OperatorDef, OperatorTable};
+    use crate::span::Span;
+    use crate::token::TokenKind;
+    use std::collections::HashMap;
+
+    fn preprocess(source: &str, config: Config) -> Vec<Result<String, LexerError>> {
+        let lexer = Lexer::with_operators(source, OperatorTable::prelude());
+        Preprocessor::new(lexer, config)
+            .map(|result| {
+                result
+                    .map(|token| token.slice.to_string())
+                    .map_err(|e| e.value)
+            })
+            .collect()
+    }
+
+    fn slices(source: &str, config: Config) -> String {
+        preprocess(source, config)
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn conditionals() {
+        let linux = Config::new().with("os", "linux").with("debug", "");
+        let source = r#"
+            a
+            $if (os == "linux") b $else c $endif
+            $if (!debug || os != linux) d $else e $endif
+            $if (unix && (debug || release)) f $endif
+            g
+        "#;
+        assert_eq!(slices(source, linux), "a b e g");
+        assert_eq!(slices(source, Config::new()), "a c d g");
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let config = Config::from_iter([("a", "")]);
+        let source = r#"
+            $if (b)
+                $if (a) 1 $else 2 $endif
+                $error("not reported")
+            $else
+                $if (a) 3 $else 4 $endif
+            $endif
+        "#;
+        assert_eq!(slices(source, config), "3");
+    }
+
+    #[test]
+    fn macros_in_dropped_branches() {
+        let source = r"
+            $if (debug)
+                @macro_rules mode { () => { debug }; }
+            $else
+                @macro_rules mode { () => { release }; }
+            $endif
+            @mode()
+        ";
+        let debug = Config::new().with("debug", "");
+        assert_eq!(slices(source, debug), "debug");
+        assert_eq!(slices(source, Config::new()), "release");
+    }
+
+    #[test]
+    fn condition_operators() {
+        let preprocess = |lexer, config| {
+            Preprocessor::new(lexer, config)
+                .map(|result| result.map(|token| token.slice).map_err(|e| e.value))
+                .collect::<Vec<_>>()
+        };
+        let config = Config::new().with("os", "linux");
+        let source = r#"
+            $if (!debug) a $endif
+            $if (debug || os==linux&&!(os != "mac" /* comment */)) b $else c $endif
+        "#;
+        assert_eq!(
+            preprocess(Lexer::new(source), config),
+            vec![Ok("a"), Ok("c")]
+        );
+        assert_eq!(
+            preprocess(Lexer::new("$if (a + b) x $endif"), Config::new()),
+            vec![Err(LexerError::InvalidDirective {
+                reason: "expected `!`, `&&`, `||`, `==` or `!=`"
+            })]
+        );
+
+        // Conditions don't depend on the operator table of the lexer
+        let config = Config::new().with("os", "linux");
+        let lexer = Lexer::with_operators(source, OperatorTable::new());
+        assert_eq!(preprocess(lexer, config.clone()), vec![Ok("a"), Ok("c")]);
+        let mut operators = OperatorTable::new();
+        operators.define("!!", OperatorDef::prefix(11)).unwrap();
+        operators.define("|||", OperatorDef::postfix(12)).unwrap();
+        let source = "$if (!!debug||os==linux) a $endif";
+        let lexer = Lexer::with_operators(source, operators);
+        assert_eq!(preprocess(lexer, config), vec![Ok("a")]);
+    }
+
+    #[test]
+    fn directive_errors() {
+        let source = r#"$else $endif $if a $endif $if (a ==) $endif $error("stop") x $if (a)"#;
+        let reason = |reason| LexerError::InvalidDirective { reason };
+        assert_eq!(
+            preprocess(source, Config::new()),
+            vec![
+                Err(LexerError::UnmatchedDirective { name: "else" }),
+                Err(LexerError::UnmatchedDirective { name: "endif" }),
+                Err(reason("expected arguments in parentheses")),
+                Err(reason("expected a value to compare with")),
+                Err(LexerError::DirectiveError {
+                    message: "stop".to_string()
+                }),
+                Ok("x".to_string()),
+                Err(LexerError::UnterminatedConditional),
+            ]
+        );
+
+        // Errors in the condition don't hide the ones after it
+        assert_eq!(
+            preprocess("$if (a ==) x $else y $else z $endif", Config::new()),
+            vec![
+                Err(reason("expected a value to compare with")),
+                Ok("y".to_string()),
+                Err(reason("`$else` after `$else`")),
+                Ok("z".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_directives_pass_through() {
+        assert_eq!(slices("$define x", Config::new()), "$define x");
+    }
+
+    #[test]
+    fn warnings() {
+        let lexer = Lexer::new(r#"a $warning("careful") b"#);
+        let mut preprocessor = Preprocessor::new(lexer, Config::new());
+        assert_eq!(preprocessor.by_ref().count(), 2);
+        let warnings = preprocessor.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].0, FileId::ROOT);
+        assert_eq!(warnings[0].1.value, "careful");
+        assert_eq!(warnings[0].1.span, Span::new(2, 21));
+        assert!(preprocessor.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn editions() {
+        let files = HashMap::from([("old.osta".to_string(), "fn")]);
+        let source = r#"$edition(2024) fn $include("old.osta")"#;
+        let mut preprocessor =
+            Preprocessor::new(Lexer::new(source), Config::new()).with_loader(files);
+        let kinds: Vec<_> = preprocessor
+            .by_ref()
+            .map(|result| result.unwrap().kind)
+            .collect();
+        // Included files don't inherit the edition of the file including them
+        assert_eq!(kinds, [TokenKind::Identifier, TokenKind::Fn]);
+        assert_eq!(source_edition(source), Some(Ok(Edition::E2024)));
+        assert_eq!(source_edition("fn $edition(2024)"), None);
+
+        let error = |source| preprocess(source, Config::new()).remove(0).unwrap_err();
+        assert_eq!(
+            error("$edition(2023)"),
+            LexerError::UnknownEdition(UnknownEdition("2023".to_string()))
+        );
+        let reason = |reason| LexerError::InvalidDirective { reason };
+        assert_eq!(
+            error(r#"$edition("2024")"#),
+            reason("expected an edition, like `2024`")
+        );
+        assert_eq!(
+            error("$edition(2024) $edition(2024)"),
+            reason("`$edition` must come before any other token of the file")
+        );
+    }
+
+    #[test]
+    fn files_of_included_tokens() {
+        let files = HashMap::from([(
+            "defs.osta".to_string(),
+            r#"@macro_rules m { ($a:tt) => { ($a x) }; } $warning("in defs")"#,
+        )]);
+        let source = r#"$include("defs.osta") @m(y) z"#;
+        let mut preprocessor =
+            Preprocessor::new(Lexer::new(source), Config::new()).with_loader(files);
+        let tokens: Vec<_> = preprocessor
+            .by_ref()
+            .map(|result| {
+                let token = result.unwrap();
+                (token.slice, token.file)
+            })
+            .collect();
+        // Tokens of the macro body belong to the file defining it, arguments to the call site
+        let (root, defs) = (FileId::ROOT, FileId(1));
+        assert_eq!(
+            tokens,
+            [
+                ("(", defs),
+                ("y", root),
+                ("x", defs),
+                (")", defs),
+                ("z", root)
+            ]
+        );
+        let warnings = preprocessor.take_warnings();
+        assert_eq!(warnings[0].0, defs);
+        assert_eq!(warnings[0].1.value, "in defs");
+    }
+
+    #[test]
+    fn includes() {
+        let files = HashMap::from([
+            (
+                "a.osta".to_string(),
+                "@macro_rules m { () => { m }; } a $include(\"b.osta\")",
+            ),
+            ("b.osta".to_string(), "b $include(\"a.osta\")"),
+        ]);
+        let source = r#"$include("a.osta") @m() $include("missing.osta") end"#;
+        let lexer = Lexer::with_operators(source, OperatorTable::prelude());
+        let mut preprocessor = Preprocessor::new(lexer, Config::new()).with_loader(files);
+
+        let mut next = || {
+            let result = preprocessor.next().unwrap();
+            (preprocessor.file(), result)
+        };
+        let (a, token) = next();
+        assert_eq!(token.unwrap().slice, "a");
+        let (b, token) = next();
+        assert_eq!(token.as_ref().unwrap().slice, "b");
+        assert_eq!(token.unwrap().file, b);
+        let (_, error) = next();
+        assert_eq!(
+            error.unwrap_err().value,
+            LexerError::IncludeFailed {
+                path: "a.osta".to_string(),
+                reason: "`a.osta` is already being included".to_string()
+            }
+        );
+        // Macros defined in included files stay defined
+        let (root, token) = next();
+        assert_eq!(token.unwrap().slice, "m");
+        assert_eq!(root, FileId::ROOT);
+        let (_, error) = next();
+        assert!(matches!(
+            error.unwrap_err().value,
+            LexerError::IncludeFailed { reason, .. } if reason == "file not found"
+        ));
+        assert_eq!(next().1.unwrap().slice, "end");
+
+        let file = preprocessor.source_file(b).unwrap();
+        assert_eq!(file.path, "b.osta");
+        assert_eq!(file.included_at, Some((a, Span::new(34, 52))));
+    }
+}
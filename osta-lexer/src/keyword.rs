@@ -0,0 +1,200 @@
+//! The keywords of the language, and the words reserved to become keywords.
+//!
+//! Keywords are introduced in an [`Edition`], and a lexer only treats the words of its edition
+//! and earlier ones as keywords, so sources written for an older edition keep lexing when new
+//! keywords are added.
+
+use crate::token::TokenKind;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// A version of the language, selecting which words are keywords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Edition {
+    /// Only the modifiers `const`, `static` and `pub`, and the primitive types.
+    E2024,
+    /// Adds keywords for items, control flow and literals, and reserves words for future use.
+    E2026,
+}
+
+impl Edition {
+    pub const LATEST: Edition = Edition::E2026;
+
+    pub const ALL: &[Edition] = &[Edition::E2024, Edition::E2026];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Edition::E2024 => "2024",
+            Edition::E2026 => "2026",
+        }
+    }
+}
+
+impl Default for Edition {
+    fn default() -> Self {
+        Self::LATEST
+    }
+}
+
+impl fmt::Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+#[error("unknown edition `{0}`, expected one of 2024, 2026")]
+pub struct UnknownEdition(pub String);
+
+impl FromStr for Edition {
+    type Err = UnknownEdition;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Edition::ALL
+            .iter()
+            .copied()
+            .find(|edition| edition.as_str() == s)
+            .ok_or_else(|| UnknownEdition(s.to_string()))
+    }
+}
+
+/// Every keyword, with the kind of token it is lexed as and the edition that introduced it.
+///
+/// Sized number types such as `u8` and `f32` are keywords in every edition, and are matched by
+/// pattern instead.
+pub const KEYWORDS: &[(&str, TokenKind, Edition)] = &[
+    // Modifiers
+    ("const", TokenKind::Const, Edition::E2024),
+    ("static", TokenKind::Static, Edition::E2024),
+    ("pub", TokenKind::Pub, Edition::E2024),
+    ("extern", TokenKind::Extern, Edition::E2026),
+    ("comptime", TokenKind::Comptime, Edition::E2026),
+    ("linear", TokenKind::Linear, Edition::E2026),
+    // Items
+    ("fn", TokenKind::Fn, Edition::E2026),
+    ("type", TokenKind::Type, Edition::E2026),
+    ("struct", TokenKind::Struct, Edition::E2026),
+    ("enum", TokenKind::Enum, Edition::E2026),
+    ("union", TokenKind::Union, Edition::E2026),
+    ("import", TokenKind::Import, Edition::E2026),
+    // Control flow
+    ("let", TokenKind::Let, Edition::E2026),
+    ("if", TokenKind::If, Edition::E2026),
+    ("else", TokenKind::Else, Edition::E2026),
+    ("while", TokenKind::While, Edition::E2026),
+    ("for", TokenKind::For, Edition::E2026),
+    ("return", TokenKind::Return, Edition::E2026),
+    // Values
+    ("true", TokenKind::True, Edition::E2026),
+    ("false", TokenKind::False, Edition::E2026),
+    ("null", TokenKind::Null, Edition::E2026),
+    // Data types
+    ("never", TokenKind::Never, Edition::E2024),
+    ("void", TokenKind::Void, Edition::E2024),
+    ("usize", TokenKind::UsizeType, Edition::E2024),
+    ("isize", TokenKind::IsizeType, Edition::E2024),
+];
+
+/// Words kept free for future keywords, with the edition that reserved them.
+///
+/// Lexing one reports [`LexerError::ReservedKeyword`](crate::LexerError::ReservedKeyword); it
+/// can still be used as a raw identifier, like `r#match`.
+pub const RESERVED: &[(&str, Edition)] = &[
+    ("as", Edition::E2026),
+    ("async", Edition::E2026),
+    ("await", Edition::E2026),
+    ("break", Edition::E2026),
+    ("continue", Edition::E2026),
+    ("defer", Edition::E2026),
+    ("do", Edition::E2026),
+    ("dyn", Edition::E2026),
+    ("impl", Edition::E2026),
+    ("in", Edition::E2026),
+    ("loop", Edition::E2026),
+    ("macro", Edition::E2026),
+    ("match", Edition::E2026),
+    ("move", Edition::E2026),
+    ("mut", Edition::E2026),
+    ("trait", Edition::E2026),
+    ("try", Edition::E2026),
+    ("typeof", Edition::E2026),
+    ("unsafe", Edition::E2026),
+    ("where", Edition::E2026),
+    ("yield", Edition::E2026),
+];
+
+/// Returns the kind of token `word` is lexed as if it is a keyword in `edition`.
+pub fn keyword(word: &str, edition: Edition) -> Option<TokenKind> {
+    KEYWORDS
+        .iter()
+        .find(|(keyword, _, since)| *keyword == word && *since <= edition)
+        .map(|(_, kind, _)| kind.clone())
+}
+
+/// Returns `word` as a `'static` string if it is reserved in `edition`.
+pub fn reserved(word: &str, edition: Edition) -> Option<&'static str> {
+    RESERVED
+        .iter()
+        .find(|(reserved, since)| *reserved == word && *since <= edition)
+        .map(|(reserved, _)| *reserved)
+}
+
+/// Returns the edition that made `kind` a keyword, or `None` if it isn't one.
+pub(crate) fn introduced_in(kind: &TokenKind) -> Option<Edition> {
+    KEYWORDS
+        .iter()
+        .find(|(_, keyword, _)| keyword == kind)
+        .map(|(_, _, since)| *since)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Edition, KEYWORDS, RESERVED, UnknownEdition, keyword, reserved};
+    use crate::lexer::{Lexer, LexerError};
+    use crate::token::TokenKind;
+
+    #[test]
+    fn table_matches_lexer() {
+        for (word, kind, since) in KEYWORDS {
+            for &edition in Edition::ALL {
+                let token = Lexer::with_edition(word, edition).next().unwrap().unwrap();
+                if edition >= *since {
+                    assert_eq!(&token.kind, kind, "`{word}` in {edition}");
+                } else {
+                    assert_eq!(token.kind, TokenKind::Identifier, "`{word}` in {edition}");
+                }
+            }
+        }
+        for (word, since) in RESERVED {
+            let error = Lexer::with_edition(word, *since)
+                .next()
+                .unwrap()
+                .unwrap_err();
+            assert_eq!(error.value, LexerError::ReservedKeyword { keyword: word });
+            assert!(keyword(word, *since).is_none());
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        assert_eq!(keyword("while", Edition::E2026), Some(TokenKind::While));
+        assert_eq!(keyword("while", Edition::E2024), None);
+        assert_eq!(keyword("const", Edition::E2024), Some(TokenKind::Const));
+        assert_eq!(keyword("While", Edition::LATEST), None);
+        assert_eq!(reserved("match", Edition::E2026), Some("match"));
+        assert_eq!(reserved("match", Edition::E2024), None);
+        assert_eq!(reserved("matches", Edition::LATEST), None);
+    }
+
+    #[test]
+    fn editions() {
+        assert_eq!("2024".parse(), Ok(Edition::E2024));
+        assert_eq!(Edition::LATEST.to_string().parse(), Ok(Edition::LATEST));
+        assert_eq!(
+            "2025".parse::<Edition>(),
+            Err(UnknownEdition("2025".to_string()))
+        );
+        assert!(Edition::E2024 < Edition::E2026);
+    }
+}
@@ -1,77 +1,388 @@
-use crate::token::{Token, TokenKind};
+use crate::directive::FileId;
+use crate::keyword::{Edition, UnknownEdition, introduced_in};
+use crate::macros::{Expander, Expansion, ExpansionId, MacroDef};
+use crate::operator::{OperatorDef, OperatorError, OperatorTable};
+use crate::span::{Span, Spanned};
+use crate::token::{NumberSuffix, Token, TokenKind};
+use crate::token_tree::Delimiter;
 use logos::Logos;
+use std::collections::VecDeque;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Error, Default, Debug, PartialEq, Clone)]
 pub enum LexerError {
+    /// Reported by the underlying state machine; [`Lexer`] turns it into
+    /// [`LexerError::UnexpectedChar`].
     #[default]
     #[error("unknown token")]
     UnknownToken,
+    #[error("unexpected character `{ch}`")]
+    UnexpectedChar { ch: char, span: Span },
     #[error("invalid integer")]
     InvalidInteger(#[from] std::num::ParseIntError),
     #[error("unterminated block comment")]
     UnterminatedBlockComment,
+    /// The string opened at `start` has no closing quote; the error covers the rest of its line.
+    #[error("unterminated string literal")]
+    UnterminatedString { start: usize },
+    #[error("unterminated character literal")]
+    UnterminatedChar { start: usize },
+    #[error("unterminated raw string, expected `\"` followed by {hashes} `#`")]
+    UnterminatedRawString { hashes: usize },
+    /// A word reserved for future use was used as an identifier.
+    #[error("`{keyword}` is a reserved keyword")]
+    ReservedKeyword { keyword: &'static str },
+    #[error("unknown operator")]
+    UnknownOperator,
+    #[error("invalid escape sequence")]
+    InvalidEscape { span: Span },
+    #[error("invalid unicode escape sequence")]
+    InvalidUnicodeEscape { span: Span },
+    #[error("non-ASCII character in byte literal")]
+    NonAsciiByte { span: Span },
+    #[error("character literals must contain exactly one character")]
+    InvalidCharLiteral,
+    /// The literal doesn't fit the type of its suffix, or the default float type if it has none.
+    #[error("literal out of range for {}", literal_type(suffix))]
+    LiteralOutOfRange { suffix: Option<NumberSuffix> },
+    /// A float suffix of a width other than 16, 32 or 64 bits, like `f80`.
+    #[error("unsupported float width `f{width}`")]
+    UnsupportedFloatWidth { width: usize },
+    #[error("unclosed delimiter `{}`", delimiter.open())]
+    UnclosedDelimiter { delimiter: Delimiter, open: Span },
+    #[error("unexpected closing delimiter `{}`", delimiter.close())]
+    UnexpectedClosingDelimiter { delimiter: Delimiter },
+    /// The group opened at `open` was closed at `close` by the wrong delimiter.
+    #[error("mismatched closing delimiter: expected `{}`, found `{}`", expected.close(), found.close())]
+    MismatchedDelimiter {
+        expected: Delimiter,
+        found: Delimiter,
+        open: Span,
+        close: Span,
+    },
+    #[error("invalid macro definition: {reason}")]
+    InvalidMacroDefinition { reason: &'static str },
+    #[error("missing arguments to macro `@{name}`")]
+    MissingMacroArguments { name: String },
+    #[error("no rules of macro `@{name}` match this invocation")]
+    NoMatchingMacroRule { name: String },
+    /// Metavariables in the same repetition of the body matched a different number of times, or
+    /// a metavariable was used outside of the repetitions it was matched in.
+    #[error("metavariables of macro `@{name}` repeat a different number of times")]
+    MacroRepetitionMismatch { name: String },
+    #[error("recursion limit reached while expanding `@{name}`")]
+    MacroRecursionLimit { name: String },
+    #[error("invalid directive: {reason}")]
+    InvalidDirective { reason: &'static str },
+    #[error("`${name}` without a matching `$if`")]
+    UnmatchedDirective { name: &'static str },
+    /// The error covers the `$if` that is never closed.
+    #[error("unterminated `$if`, expected `$endif`")]
+    UnterminatedConditional,
+    #[error("couldn't include `{path}`: {reason}")]
+    IncludeFailed { path: String, reason: String },
+    /// Reported by an `$error` directive.
+    #[error("{message}")]
+    DirectiveError { message: String },
+    #[error(transparent)]
+    UnknownEdition(#[from] UnknownEdition),
 }
 
-pub type TokenResult<'src> = Result<Token<'src>, LexerError>;
+/// State shared with the token callbacks.
+#[derive(Default, Clone)]
+pub struct LexerExtras {
+    pub(crate) operators: Arc<OperatorTable>,
+    pub(crate) edition: Edition,
+    /// Errors found in the last token after the one its callback returned, reported next.
+    pub(crate) errors: VecDeque<Spanned<LexerError>>,
+    /// The file tokens are read from.
+    pub(crate) file: FileId,
+}
+
+/// Names the type of a literal with `suffix`, for error messages.
+fn literal_type(suffix: &Option<NumberSuffix>) -> String {
+    match suffix {
+        Some(suffix) => format!("`{suffix}`"),
+        None => "the default float type".to_string(),
+    }
+}
+
+pub type TokenResult<'src> = Result<Token<'src>, Spanned<LexerError>>;
 
 pub struct Lexer<'src> {
     stream: ::logos::Lexer<'src, TokenKind>,
-    queue: Vec<TokenResult<'src>>,
+    lookahead: VecDeque<TokenResult<'src>>,
+    prev_span: Span,
+    trivia: bool,
+    expander: Expander<'src>,
+}
+
+/// A snapshot of the lexer position, created by [`Lexer::checkpoint`] and restored by
+/// [`Lexer::rewind`].
+#[derive(Clone)]
+pub struct Checkpoint<'src> {
+    stream: ::logos::Lexer<'src, TokenKind>,
+    lookahead: VecDeque<TokenResult<'src>>,
+    prev_span: Span,
+    expander: Expander<'src>,
 }
 
 impl<'src> Lexer<'src> {
+    /// Creates a lexer with the operators of [`OperatorTable::prelude`], see
+    /// [`Lexer::with_operators`] to use others.
     pub fn new(source: &'src str) -> Self {
+        Self::with_operators(source, OperatorTable::prelude())
+    }
+
+    /// Creates a lexer that splits runs of operator characters using `operators`.
+    pub fn with_operators(source: &'src str, operators: impl Into<Arc<OperatorTable>>) -> Self {
+        let extras = LexerExtras {
+            operators: operators.into(),
+            edition: Edition::default(),
+            errors: VecDeque::new(),
+            file: FileId::ROOT,
+        };
         Self {
-            stream: TokenKind::lexer(source),
-            queue: Vec::new(),
+            stream: TokenKind::lexer_with_extras(source, extras),
+            lookahead: VecDeque::new(),
+            prev_span: Span::default(),
+            trivia: false,
+            expander: Expander::default(),
         }
     }
 
+    /// Creates a lexer with the prelude operators that treats the keywords of `edition` as
+    /// keywords, see [`Lexer::set_edition`].
+    pub fn with_edition(source: &'src str, edition: Edition) -> Self {
+        let mut lexer = Self::new(source);
+        lexer.set_edition(edition);
+        lexer
+    }
+
+    /// Creates a lossless lexer with the prelude operators, see [`Lexer::set_trivia`].
+    pub fn with_trivia(source: &'src str) -> Self {
+        let mut lexer = Self::new(source);
+        lexer.set_trivia(true);
+        lexer
+    }
+
+    /// Sets whether [`TokenKind::Whitespace`] and [`TokenKind::Newline`] tokens are emitted.
+    ///
+    /// Comments are always emitted, so with trivia enabled the slices of all tokens and errors
+    /// concatenate back into the source. Macros are not expanded either, since expansions have
+    /// no source text of their own. Tokens already in the lookahead buffer are not affected.
+    pub fn set_trivia(&mut self, trivia: bool) {
+        self.trivia = trivia;
+    }
+
+    pub fn edition(&self) -> Edition {
+        self.stream.extras.edition
+    }
+
+    /// Sets the edition selecting which words are keywords, which defaults to
+    /// [`Edition::LATEST`]. Keywords of later editions are lexed as identifiers, and the words
+    /// they reserve are allowed.
+    ///
+    /// Tokens already in the lookahead buffer are not affected.
+    pub fn set_edition(&mut self, edition: Edition) {
+        self.stream.extras.edition = edition;
+    }
+
+    /// Sets the file the tokens read from now on are marked with.
+    pub(crate) fn set_file(&mut self, file: FileId) {
+        self.stream.extras.file = file;
+    }
+
+    /// Skips to `offset` in the source, which must be at or after the current position.
+    pub(crate) fn seek(&mut self, offset: usize) {
+        self.stream.bump(offset - self.stream.span().end);
+    }
+
+    pub fn operators(&self) -> &OperatorTable {
+        &self.stream.extras.operators
+    }
+
+    /// Defines a new operator, returning its id.
+    ///
+    /// Tokens already in the lookahead buffer are not affected.
+    pub fn define_operator(
+        &mut self,
+        spelling: &str,
+        def: OperatorDef,
+    ) -> Result<usize, OperatorError> {
+        Arc::make_mut(&mut self.stream.extras.operators).define(spelling, def)
+    }
+
+    /// Exchanges the operators, macros and trivia setting with `other`, so a lexer for another
+    /// source can continue where this one left off.
+    pub(crate) fn swap_state(&mut self, other: &mut Lexer<'src>) {
+        std::mem::swap(
+            &mut self.stream.extras.operators,
+            &mut other.stream.extras.operators,
+        );
+        std::mem::swap(&mut self.trivia, &mut other.trivia);
+        self.expander.swap_definitions(&mut other.expander);
+    }
+
+    /// Returns the macro defined with `name`, if it has been read by the lexer yet.
+    pub fn macro_def(&self, name: &str) -> Option<&MacroDef<'src>> {
+        self.expander.get(name)
+    }
+
+    /// Returns where the tokens marked with `id` were expanded, or `None` for
+    /// [`ExpansionId::ROOT`].
+    pub fn expansion(&self, id: ExpansionId) -> Option<&Expansion> {
+        self.expander.expansion(id)
+    }
+
+    /// Returns the expansions `id` is nested in, innermost first.
+    pub fn backtrace(&self, id: ExpansionId) -> impl Iterator<Item = &Expansion> {
+        std::iter::successors(self.expansion(id), |expansion| {
+            self.expansion(expansion.parent)
+        })
+    }
+
+    pub fn source(&self) -> &'src str {
+        self.stream.source()
+    }
+
+    /// Returns the `n`-th upcoming token without consuming it, `peek(0)` being the token the next
+    /// call to [`Lexer::bump`] will return.
     pub fn peek(&mut self, n: usize) -> Option<&TokenResult<'src>> {
-        while self.queue.len() <= n {
-            if let Some(result) = self.next() {
-                self.queue.push(result)
-            } else {
-                return None;
-            }
+        while self.lookahead.len() <= n {
+            let result = self.inner_next()?;
+            self.lookahead.push_back(result);
+        }
+        self.lookahead.get(n)
+    }
+
+    /// Returns the kind of the `n`-th upcoming token, or `None` if it is an error or past the end
+    /// of the input.
+    pub fn peek_kind(&mut self, n: usize) -> Option<&TokenKind> {
+        match self.peek(n) {
+            Some(Ok(token)) => Some(&token.kind),
+            _ => None,
+        }
+    }
+
+    /// Consumes and returns the next token.
+    pub fn bump(&mut self) -> Option<TokenResult<'src>> {
+        let result = self.lookahead.pop_front().or_else(|| self.inner_next())?;
+        Some(self.consumed(result))
+    }
+
+    /// Consumes and returns the next token without expanding macros, so neither definitions nor
+    /// invocations take effect. Tokens already peeked or expanded are still returned first.
+    pub(crate) fn bump_unexpanded(&mut self) -> Option<TokenResult<'src>> {
+        let result = match self.lookahead.pop_front() {
+            Some(result) => result,
+            None => self
+                .expander
+                .next_pending()
+                .or_else(|| raw_next(&mut self.stream, self.trivia))?,
+        };
+        Some(self.consumed(result))
+    }
+
+    fn consumed(&mut self, result: TokenResult<'src>) -> TokenResult<'src> {
+        self.prev_span = match &result {
+            Ok(token) => token.span,
+            Err(error) => error.span,
+        };
+        result
+    }
+
+    /// Returns the span of the last token returned by [`Lexer::bump`].
+    pub fn prev_span(&self) -> Span {
+        self.prev_span
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint<'src> {
+        Checkpoint {
+            stream: self.stream.clone(),
+            lookahead: self.lookahead.clone(),
+            prev_span: self.prev_span,
+            expander: self.expander.clone(),
         }
-        self.queue.get(n)
+    }
+
+    /// Restores the lexer to a previously taken [`Checkpoint`], so every token consumed since then
+    /// is returned again.
+    pub fn rewind(&mut self, checkpoint: Checkpoint<'src>) {
+        self.stream = checkpoint.stream;
+        self.lookahead = checkpoint.lookahead;
+        self.prev_span = checkpoint.prev_span;
+        self.expander = checkpoint.expander;
     }
 
     #[cfg(test)]
     pub(crate) fn slice(&self) -> &'src str {
-        self.stream.slice()
+        &self.source()[self.prev_span.range()]
     }
 
     fn inner_next(&mut self) -> Option<TokenResult<'src>> {
-        if let Some(result) = self.queue.pop() {
-            Some(result)
-        } else if let Some(result) = self.stream.next() {
-            Some(
-                result
-                    .map(|kind| Token::new(kind, self.stream.slice()))
-                    .map_err(|e| e.into()),
-            )
-        } else {
-            None
+        let (stream, trivia) = (&mut self.stream, self.trivia);
+        if trivia {
+            return raw_next(stream, trivia);
+        }
+        let (source, operators) = (stream.source(), Arc::clone(&stream.extras.operators));
+        self.expander
+            .next(source, &operators, &mut || raw_next(stream, trivia))
+    }
+}
+
+/// Returns the next token of `stream`, before macro expansion.
+fn raw_next<'src>(
+    stream: &mut ::logos::Lexer<'src, TokenKind>,
+    trivia: bool,
+) -> Option<TokenResult<'src>> {
+    if let Some(error) = stream.extras.errors.pop_front() {
+        return Some(Err(error));
+    }
+    let result = loop {
+        match stream.next()? {
+            Ok(TokenKind::Whitespace | TokenKind::Newline) if !trivia => continue,
+            result => break result,
         }
+    };
+    let mut span = Span::from(stream.span());
+    // An error followed by queued ones only covers the token up to the first of them
+    if let Some(queued) = stream.extras.errors.front() {
+        span.end = queued.span.start;
     }
+    let slice = stream.slice();
+    let (edition, file) = (stream.extras.edition, stream.extras.file);
+    let token = |kind| Token {
+        file,
+        ..Token::new(kind, slice, span)
+    };
+    Some(match result {
+        Ok(kind) if edition < Edition::LATEST && introduced_in(&kind) > Some(edition) => {
+            Ok(token(TokenKind::Identifier))
+        }
+        Ok(kind) => Ok(token(kind)),
+        Err(LexerError::UnknownToken) => {
+            let ch = slice.chars().next().unwrap_or_default();
+            Err(Spanned::new(LexerError::UnexpectedChar { ch, span }, span))
+        }
+        Err(e) => Err(Spanned::new(e, span)),
+    })
 }
 
 impl<'src> Iterator for Lexer<'src> {
     type Item = TokenResult<'src>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner_next()
-        // TODO(johan): add macro expansion here
-        /*
-        TODO(johan): implement operators here
-        Operators in osta are not necessarily hardcoded tokens, they can be sequences of other
-        tokens. For example, the operator `->` is hardcoded, but `+` is not, it must be emitted
-        redirecting the unrecognized token as an operator token. The token `++` can collide with
-        `+`, and this patterns can be arbitrarily long and unknown by us, so we must consider a
-        Trie structure or an ART to store operators and match them greedily.
-        */
+        self.bump()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let buffered = n.min(self.lookahead.len());
+        self.lookahead.drain(..buffered);
+        for _ in buffered..n {
+            let _ = self.bump()?;
+        }
+        self.bump()
     }
 }
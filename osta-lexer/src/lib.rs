@@ -1,13 +1,33 @@
+pub mod directive;
+pub mod keyword;
 pub mod lexer;
+pub mod line_index;
+pub mod literal;
+pub mod macros;
+pub mod operator;
+pub mod span;
 pub mod token;
+pub mod token_tree;
 
-pub use lexer::{Lexer, LexerError};
-pub use token::{Token, TokenKind};
+pub use directive::{Config, FileId, Preprocessor, SourceFile, SourceLoader, source_edition};
+pub use keyword::{Edition, UnknownEdition};
+pub use lexer::{Checkpoint, Lexer, LexerError};
+pub use line_index::{Encoding, LineCol, LineIndex};
+pub use literal::LiteralError;
+pub use macros::{Expansion, ExpansionId, MacroDef};
+pub use operator::{Associativity, Fixity, OperatorDef, OperatorTable, OperatorTrie};
+pub use span::{Span, Spanned};
+pub use token::{NumberSuffix, Token, TokenKind};
+pub use token_tree::{Delimiter, Group, TokenTree};
 
 #[cfg(test)]
 mod tests {
-    use crate::lexer::Lexer;
-    use crate::token::{Token, TokenKind};
+    use crate::keyword::Edition;
+    use crate::lexer::{Lexer, LexerError};
+    use crate::literal::LiteralError;
+    use crate::operator::{OperatorDef, OperatorTable};
+    use crate::span::{Span, Spanned};
+    use crate::token::{NumberSuffix, Token, TokenKind};
 
     macro_rules! assert_lex_inner {
         ($lexer:ident, token, $pattern:pat) => {
@@ -147,13 +167,103 @@ mod tests {
         */",
         kind @ TokenKind::Comment
     );
+    test_lex!(
+        doc_comments,
+        r"
+        /// outer
+        //! inner
+        //// not a doc comment
+        /** outer block */
+        /*! inner /* nested */ block */
+        /**/ /***/ /*** not a doc comment */
+        ",
+        kind @ TokenKind::OuterDocComment => "/// outer",
+        kind @ TokenKind::InnerDocComment => "//! inner",
+        kind @ TokenKind::Comment => "//// not a doc comment",
+        kind @ TokenKind::OuterDocComment => "/** outer block */",
+        kind @ TokenKind::InnerDocComment => "/*! inner /* nested */ block */",
+        kind @ TokenKind::Comment => "/**/",
+        kind @ TokenKind::Comment => "/***/",
+        kind @ TokenKind::Comment => "/*** not a doc comment */"
+    );
+
+    #[test]
+    fn doc_text() {
+        let texts = Lexer::new("/// outer\n//! inner\n/** block\n */ /*!*/ // comment")
+            .map(|result| result.unwrap().doc_text())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                Some(" outer"),
+                Some(" inner"),
+                Some(" block\n "),
+                Some(""),
+                None
+            ]
+        );
+    }
+
     test_lex!(
         keywords,
-        "const static pub",
+        "const static pub fn type let if else while return true null",
         kind @ TokenKind::Const,
         kind @ TokenKind::Static,
-        kind @ TokenKind::Pub
+        kind @ TokenKind::Pub,
+        kind @ TokenKind::Fn,
+        kind @ TokenKind::Type,
+        kind @ TokenKind::Let,
+        kind @ TokenKind::If,
+        kind @ TokenKind::Else,
+        kind @ TokenKind::While,
+        kind @ TokenKind::Return,
+        kind @ TokenKind::True,
+        kind @ TokenKind::Null
     );
+    test_lex!(
+        keyword_prefixes,
+        "fnord types iff r#fn r#match r#x",
+        token @ Token { kind: TokenKind::Identifier, slice: "fnord", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "types", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "iff", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "r#fn", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "r#match", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "r#x", .. }
+    );
+
+    #[test]
+    fn reserved_keywords() {
+        let mut lexer = Lexer::new("match x r#match");
+        let error = lexer.next().unwrap().unwrap_err();
+        assert_eq!(
+            error.value,
+            LexerError::ReservedKeyword { keyword: "match" }
+        );
+        assert_eq!(error.span, Span::new(0, 5));
+        assert_eq!(lexer.next().unwrap().unwrap().ident_name(), "x");
+        assert_eq!(lexer.next().unwrap().unwrap().ident_name(), "match");
+    }
+
+    #[test]
+    fn editions() {
+        let kinds = |lexer: Lexer| -> Vec<_> { lexer.map(|result| result.unwrap().kind).collect() };
+        let source = "const fn match";
+        assert_eq!(
+            kinds(Lexer::with_edition(source, Edition::E2024)),
+            [
+                TokenKind::Const,
+                TokenKind::Identifier,
+                TokenKind::Identifier
+            ]
+        );
+
+        let mut lexer = Lexer::with_edition(source, Edition::E2024);
+        lexer.next();
+        lexer.set_edition(Edition::E2026);
+        assert_eq!(lexer.next().unwrap().unwrap().kind, TokenKind::Fn);
+        assert!(lexer.next().unwrap().is_err());
+        assert_eq!(Lexer::new(source).edition(), Edition::LATEST);
+    }
     test_lex!(
         primitives,
         "never void i1 i8 i16 i31 i32 i64 i128 isize u1 u8 u16 u31 u32 u64 u128 usize f16 f32 f64",
@@ -182,13 +292,13 @@ mod tests {
     test_lex!(
         identifiers,
         "foo _foo foo123 i32_ @macro_name #comptime_name $directive_name",
-        token @ Token { kind: TokenKind::Identifier, slice: "foo" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_foo" },
-        token @ Token { kind: TokenKind::Identifier, slice: "foo123" },
-        token @ Token { kind: TokenKind::Identifier, slice: "i32_" },
-        token @ Token { kind: TokenKind::MacroIdentifier, slice: "@macro_name" },
-        token @ Token { kind: TokenKind::ComptimeIdentifier, slice: "#comptime_name" },
-        token @ Token { kind: TokenKind::DirectiveIdentifier, slice: "$directive_name" }
+        token @ Token { kind: TokenKind::Identifier, slice: "foo", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_foo", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "foo123", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "i32_", .. },
+        token @ Token { kind: TokenKind::MacroIdentifier, slice: "@macro_name", .. },
+        token @ Token { kind: TokenKind::ComptimeIdentifier, slice: "#comptime_name", .. },
+        token @ Token { kind: TokenKind::DirectiveIdentifier, slice: "$directive_name", .. }
     );
     test_lex!(
         integers,
@@ -198,40 +308,134 @@ mod tests {
         0o7755 0O7755 0o77_55 0O7_7_5_5 0o77__55 0O_7755 0o7755_
         0xAA55 0XAA55 0xAA_55 0XA_A_5_5 0xAA__55 0X_AA55 0xAA55_
         ",
-        token @ Token { kind: TokenKind::DecInt, slice: "123" },
-        token @ Token { kind: TokenKind::DecInt, slice: "1_2_3" },
-        token @ Token { kind: TokenKind::DecInt, slice: "1__2__3" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_123" },
-        token @ Token { kind: TokenKind::DecInt, slice: "123" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_" },
-        token @ Token { kind: TokenKind::BinInt, slice: "0b1010" },
-        token @ Token { kind: TokenKind::BinInt, slice: "0B1010" },
-        token @ Token { kind: TokenKind::BinInt, slice: "0b10_10" },
-        token @ Token { kind: TokenKind::BinInt, slice: "0B1_0_1_0" },
-        token @ Token { kind: TokenKind::BinInt, slice: "0b10__10" },
-        token @ Token { kind: TokenKind::DecInt, slice: "0" },
-        token @ Token { kind: TokenKind::Identifier, slice: "B_1010" },
-        token @ Token { kind: TokenKind::BinInt, slice: "0b1010" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_" },
-        token @ Token { kind: TokenKind::OctInt, slice: "0o7755" },
-        token @ Token { kind: TokenKind::OctInt, slice: "0O7755" },
-        token @ Token { kind: TokenKind::OctInt, slice: "0o77_55" },
-        token @ Token { kind: TokenKind::OctInt, slice: "0O7_7_5_5" },
-        token @ Token { kind: TokenKind::OctInt, slice: "0o77__55" },
-        token @ Token { kind: TokenKind::DecInt, slice: "0" },
-        token @ Token { kind: TokenKind::Identifier, slice: "O_7755" },
-        token @ Token { kind: TokenKind::OctInt, slice: "0o7755" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_" },
-        token @ Token { kind: TokenKind::HexInt, slice: "0xAA55" },
-        token @ Token { kind: TokenKind::HexInt, slice: "0XAA55" },
-        token @ Token { kind: TokenKind::HexInt, slice: "0xAA_55" },
-        token @ Token { kind: TokenKind::HexInt, slice: "0XA_A_5_5" },
-        token @ Token { kind: TokenKind::HexInt, slice: "0xAA__55" },
-        token @ Token { kind: TokenKind::DecInt, slice: "0" },
-        token @ Token { kind: TokenKind::Identifier, slice: "X_AA55" },
-        token @ Token { kind: TokenKind::HexInt, slice: "0xAA55" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_" }
+        token @ Token { kind: TokenKind::DecInt(None), slice: "123", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "1_2_3", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "1__2__3", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_123", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "123", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_", .. },
+        token @ Token { kind: TokenKind::BinInt(None), slice: "0b1010", .. },
+        token @ Token { kind: TokenKind::BinInt(None), slice: "0B1010", .. },
+        token @ Token { kind: TokenKind::BinInt(None), slice: "0b10_10", .. },
+        token @ Token { kind: TokenKind::BinInt(None), slice: "0B1_0_1_0", .. },
+        token @ Token { kind: TokenKind::BinInt(None), slice: "0b10__10", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "0", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "B_1010", .. },
+        token @ Token { kind: TokenKind::BinInt(None), slice: "0b1010", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_", .. },
+        token @ Token { kind: TokenKind::OctInt(None), slice: "0o7755", .. },
+        token @ Token { kind: TokenKind::OctInt(None), slice: "0O7755", .. },
+        token @ Token { kind: TokenKind::OctInt(None), slice: "0o77_55", .. },
+        token @ Token { kind: TokenKind::OctInt(None), slice: "0O7_7_5_5", .. },
+        token @ Token { kind: TokenKind::OctInt(None), slice: "0o77__55", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "0", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "O_7755", .. },
+        token @ Token { kind: TokenKind::OctInt(None), slice: "0o7755", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_", .. },
+        token @ Token { kind: TokenKind::HexInt(None), slice: "0xAA55", .. },
+        token @ Token { kind: TokenKind::HexInt(None), slice: "0XAA55", .. },
+        token @ Token { kind: TokenKind::HexInt(None), slice: "0xAA_55", .. },
+        token @ Token { kind: TokenKind::HexInt(None), slice: "0XA_A_5_5", .. },
+        token @ Token { kind: TokenKind::HexInt(None), slice: "0xAA__55", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "0", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "X_AA55", .. },
+        token @ Token { kind: TokenKind::HexInt(None), slice: "0xAA55", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_", .. }
+    );
+    test_lex!(
+        suffixes,
+        r"
+        255u8 0u1 1u1 127i8 128i8 1usize 0xFFu8 0b1i1 0o777u16 1i128 1u999
+        1.5f32 1e3f64 1.0e-3f16 2f16 65504f16 1u08
+        256u8 2u1 129i8 0x1_00u8 1e39f32 65520f16 1e400f64 1.5f80
+        1u99999999999999999999999
+        ",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Uint(8))) => "255u8",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Uint(1))) => "0u1",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Uint(1))) => "1u1",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Int(8))) => "127i8",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Int(8))) => "128i8",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Usize)) => "1usize",
+        kind @ TokenKind::HexInt(Some(NumberSuffix::Uint(8))) => "0xFFu8",
+        kind @ TokenKind::BinInt(Some(NumberSuffix::Int(1))) => "0b1i1",
+        kind @ TokenKind::OctInt(Some(NumberSuffix::Uint(16))) => "0o777u16",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Int(128))) => "1i128",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Uint(999))) => "1u999",
+        kind @ TokenKind::Float(Some(NumberSuffix::Float(32))) => "1.5f32",
+        kind @ TokenKind::IntExp(Some(NumberSuffix::Float(64))) => "1e3f64",
+        kind @ TokenKind::FloatExp(Some(NumberSuffix::Float(16))) => "1.0e-3f16",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Float(16))) => "2f16",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Float(16))) => "65504f16",
+        kind @ TokenKind::DecInt(Some(NumberSuffix::Uint(8))) => "1u08",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { suffix: Some(NumberSuffix::Uint(8)) }, .. } => "256u8",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { suffix: Some(NumberSuffix::Uint(1)) }, .. } => "2u1",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { suffix: Some(NumberSuffix::Int(8)) }, .. } => "129i8",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { .. }, .. } => "0x1_00u8",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { suffix: Some(NumberSuffix::Float(32)) }, .. } => "1e39f32",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { .. }, .. } => "65520f16",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { .. }, .. } => "1e400f64",
+        error @ Spanned { value: LexerError::UnsupportedFloatWidth { width: 80 }, .. } => "1.5f80",
+        error @ Spanned { value: LexerError::InvalidInteger(_), .. } => "1u99999999999999999999999"
     );
+    test_lex!(
+        hex_floats,
+        r"
+        0x1.8p3 0X1P-2 0xA_B.C_Dp+1_0 0x1.p0 0x1.fffffep127f32 0x1.ffcp15f16 0x1p3f128
+        0x1p128f32 0x1p1024 0x1.8 0x1p 0x1pf32
+        ",
+        token @ Token { kind: TokenKind::HexFloat(None), slice: "0x1.8p3", .. },
+        token @ Token { kind: TokenKind::HexFloat(None), slice: "0X1P-2", .. },
+        token @ Token { kind: TokenKind::HexFloat(None), slice: "0xA_B.C_Dp+1_0", .. },
+        token @ Token { kind: TokenKind::HexFloat(None), slice: "0x1.p0", .. },
+        kind @ TokenKind::HexFloat(Some(NumberSuffix::Float(32))) => "0x1.fffffep127f32",
+        kind @ TokenKind::HexFloat(Some(NumberSuffix::Float(16))) => "0x1.ffcp15f16",
+        error @ Spanned { value: LexerError::UnsupportedFloatWidth { width: 128 }, .. } => "0x1p3f128",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { suffix: Some(NumberSuffix::Float(32)) }, .. } => "0x1p128f32",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { suffix: None }, .. } => "0x1p1024",
+        kind @ TokenKind::HexInt(None) => "0x1",
+        error => ".",
+        kind @ TokenKind::DecInt(None) => "8",
+        kind @ TokenKind::HexInt(None) => "0x1",
+        kind @ TokenKind::Identifier => "p",
+        kind @ TokenKind::HexInt(None) => "0x1",
+        kind @ TokenKind::Identifier => "pf32"
+    );
+
+    #[test]
+    fn hex_float_values() {
+        let mut lexer = Lexer::new("0x1.8p3 0x1.000001p0f32 0x1.ffcp15f16");
+        let mut next = || lexer.next().unwrap().unwrap();
+        assert_eq!(next().float_value(), Ok(12.0));
+        let token = next();
+        assert_eq!(token.hex_float_bits(), Ok(1f32.to_bits() as u64));
+        assert_eq!(token.float_value(), Ok(1.0));
+        let token = next();
+        assert_eq!(token.hex_float_bits(), Ok(0x7bff));
+        assert_eq!(token.float_value(), Ok(65504.0));
+    }
+
+    test_lex!(
+        bin_floats,
+        "0b1.1p3 0B1_0.p-1 0b1p-24f16 0b1p128f32 0b1p1024 0b1p0f8",
+        token @ Token { kind: TokenKind::BinFloat(None), slice: "0b1.1p3", .. },
+        token @ Token { kind: TokenKind::BinFloat(None), slice: "0B1_0.p-1", .. },
+        kind @ TokenKind::BinFloat(Some(NumberSuffix::Float(16))) => "0b1p-24f16",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { suffix: Some(NumberSuffix::Float(32)) }, .. } => "0b1p128f32",
+        error @ Spanned { value: LexerError::LiteralOutOfRange { suffix: None }, .. } => "0b1p1024",
+        error @ Spanned { value: LexerError::UnsupportedFloatWidth { width: 8 }, .. } => "0b1p0f8"
+    );
+
+    #[test]
+    fn bin_float_values() {
+        let mut lexer = Lexer::new("0b1.1p3 0b1p-24f16");
+        let mut next = || lexer.next().unwrap().unwrap();
+        assert_eq!(next().float_value(), Ok(12.0));
+        let token = next();
+        assert_eq!(token.bin_float_bits(), Ok(1));
+        assert!(token.hex_float_bits().is_err());
+        assert_eq!(token.float_value(), Ok(2f64.powi(-24)));
+    }
+
     test_lex!(
         floats,
         r"
@@ -241,53 +445,53 @@ mod tests {
         1e10    1E10    1e-10    1E-10    1e+10    1E+10
         _1e10   1_E10   1e-_10   1E-1_0   1e+10_   1E_10
         ",
-        token @ Token { kind: TokenKind::Float, slice: "1.0" },
-        token @ Token { kind: TokenKind::IntFloat, slice: "1." },
-        token @ Token { kind: TokenKind::FloatExp, slice: "1.0e10" },
-        token @ Token { kind: TokenKind::FloatExp, slice: "1.0E10" },
-        token @ Token { kind: TokenKind::FloatExp, slice: "1.0e-10" },
-        token @ Token { kind: TokenKind::FloatExp, slice: "1.0E-10" },
-        token @ Token { kind: TokenKind::FloatExp, slice: "1.0e+10" },
-        token @ Token { kind: TokenKind::FloatExp, slice: "1.0E+10" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_1" },
+        token @ Token { kind: TokenKind::Float(None), slice: "1.0", .. },
+        token @ Token { kind: TokenKind::IntFloat, slice: "1.", .. },
+        token @ Token { kind: TokenKind::FloatExp(None), slice: "1.0e10", .. },
+        token @ Token { kind: TokenKind::FloatExp(None), slice: "1.0E10", .. },
+        token @ Token { kind: TokenKind::FloatExp(None), slice: "1.0e-10", .. },
+        token @ Token { kind: TokenKind::FloatExp(None), slice: "1.0E-10", .. },
+        token @ Token { kind: TokenKind::FloatExp(None), slice: "1.0e+10", .. },
+        token @ Token { kind: TokenKind::FloatExp(None), slice: "1.0E+10", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_1", .. },
         error => ".",
-        token @ Token { kind: TokenKind::IntExp, slice: "0e10" },
-        token @ Token { kind: TokenKind::DecInt, slice: "1" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_" },
+        token @ Token { kind: TokenKind::IntExp(None), slice: "0e10", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "1", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_", .. },
         error => ".",
-        token @ Token { kind: TokenKind::IntExp, slice: "0E10" },
-        token @ Token { kind: TokenKind::IntFloat, slice: "1." },
-        token @ Token { kind: TokenKind::Identifier, slice: "_0e" },
-        error => "-",
-        token @ Token { kind: TokenKind::DecInt, slice: "10" },
-        token @ Token { kind: TokenKind::Float, slice: "1.0" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_E" },
-        error => "-",
-        token @ Token { kind: TokenKind::DecInt, slice: "10" },
-        token @ Token { kind: TokenKind::Float, slice: "1.0" },
-        token @ Token { kind: TokenKind::Identifier, slice: "e" },
-        error => "+",
-        token @ Token { kind: TokenKind::Identifier, slice: "_10" },
-        token @ Token { kind: TokenKind::FloatExp, slice: "1.0E+10" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_" },
-        token @ Token { kind: TokenKind::IntExp, slice: "1e10" },
-        token @ Token { kind: TokenKind::IntExp, slice: "1E10" },
-        token @ Token { kind: TokenKind::IntExp, slice: "1e-10" },
-        token @ Token { kind: TokenKind::IntExp, slice: "1E-10" },
-        token @ Token { kind: TokenKind::IntExp, slice: "1e+10" },
-        token @ Token { kind: TokenKind::IntExp, slice: "1E+10" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_1e10" },
-        token @ Token { kind: TokenKind::DecInt, slice: "1" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_E10" },
-        token @ Token { kind: TokenKind::DecInt, slice: "1" },
-        token @ Token { kind: TokenKind::Identifier, slice: "e" },
-        error => "-",
-        token @ Token { kind: TokenKind::Identifier, slice: "_10" },
-        token @ Token { kind: TokenKind::IntExp, slice: "1E-1_0" },
-        token @ Token { kind: TokenKind::IntExp, slice: "1e+10" },
-        token @ Token { kind: TokenKind::Identifier, slice: "_" },
-        token @ Token { kind: TokenKind::DecInt, slice: "1" },
-        token @ Token { kind: TokenKind::Identifier, slice: "E_10" }
+        token @ Token { kind: TokenKind::IntExp(None), slice: "0E10", .. },
+        token @ Token { kind: TokenKind::IntFloat, slice: "1.", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_0e", .. },
+        kind @ TokenKind::Operator(_) => "-",
+        token @ Token { kind: TokenKind::DecInt(None), slice: "10", .. },
+        token @ Token { kind: TokenKind::Float(None), slice: "1.0", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_E", .. },
+        kind @ TokenKind::Operator(_) => "-",
+        token @ Token { kind: TokenKind::DecInt(None), slice: "10", .. },
+        token @ Token { kind: TokenKind::Float(None), slice: "1.0", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "e", .. },
+        kind @ TokenKind::Operator(_) => "+",
+        token @ Token { kind: TokenKind::Identifier, slice: "_10", .. },
+        token @ Token { kind: TokenKind::FloatExp(None), slice: "1.0E+10", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_", .. },
+        token @ Token { kind: TokenKind::IntExp(None), slice: "1e10", .. },
+        token @ Token { kind: TokenKind::IntExp(None), slice: "1E10", .. },
+        token @ Token { kind: TokenKind::IntExp(None), slice: "1e-10", .. },
+        token @ Token { kind: TokenKind::IntExp(None), slice: "1E-10", .. },
+        token @ Token { kind: TokenKind::IntExp(None), slice: "1e+10", .. },
+        token @ Token { kind: TokenKind::IntExp(None), slice: "1E+10", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_1e10", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "1", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_E10", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "1", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "e", .. },
+        kind @ TokenKind::Operator(_) => "-",
+        token @ Token { kind: TokenKind::Identifier, slice: "_10", .. },
+        token @ Token { kind: TokenKind::IntExp(None), slice: "1E-1_0", .. },
+        token @ Token { kind: TokenKind::IntExp(None), slice: "1e+10", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "_", .. },
+        token @ Token { kind: TokenKind::DecInt(None), slice: "1", .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "E_10", .. }
     );
     test_lex!(
         strings,
@@ -297,34 +501,148 @@ mod tests {
         r##"this is a raw string with "# in it"##
         r###"this is a raw string with ##" in it"###
         "####,
-        token @ Token { kind: TokenKind::String, slice: r#""this is a string""# },
-        token @ Token { kind: TokenKind::String, slice: r#""this is a \"string\" with escapes""# },
-        token @ Token { kind: TokenKind::String, slice: r#""""# },
-        token @ Token { kind: TokenKind::RawString, slice: r##"r#"this is a raw string"#"## },
-        token @ Token { kind: TokenKind::RawString, slice: r###"r##"this is a raw string with "# in it"##"### },
-        token @ Token { kind: TokenKind::RawString, slice: r####"r###"this is a raw string with ##" in it"###"#### }
+        token @ Token { kind: TokenKind::String, slice: r#""this is a string""#, .. },
+        token @ Token { kind: TokenKind::String, slice: r#""this is a \"string\" with escapes""#, .. },
+        token @ Token { kind: TokenKind::String, slice: r#""""#, .. },
+        token @ Token { kind: TokenKind::RawString, slice: r##"r#"this is a raw string"#"##, .. },
+        token @ Token { kind: TokenKind::RawString, slice: r###"r##"this is a raw string with "# in it"##"###, .. },
+        token @ Token { kind: TokenKind::RawString, slice: r####"r###"this is a raw string with ##" in it"###"####, .. }
+    );
+    // Raw strings don't process escapes, so `\"` closes them like any other quote
+    test_lex!(
+        raw_string_backslash,
+        r#"r"a\"b" x"#,
+        token @ Token { kind: TokenKind::RawString, slice: r#"r"a\""#, .. },
+        error @ Spanned { value: LexerError::UnterminatedString { start: 5 }, .. } => r#"b" x"#
+    );
+    test_lex!(
+        string_escapes,
+        r#"
+        "\n\r\t\\\"\'\0\x7F\u{0}\u{10FFFF}"
+        "\q" "a\x80" "\x4" "\u{D800}" "\u{110000}" "\u{}" "\u{1234567}" "\u{41" "\u41"
+        "\e \x" ok
+        "#,
+        kind @ TokenKind::String,
+        error @ Spanned { value: LexerError::InvalidEscape { span: Span { start: 45, end: 47 } }, .. } => r#""\q""#,
+        error @ Spanned { value: LexerError::InvalidEscape { span: Span { start: 51, end: 55 } }, .. },
+        error @ Spanned { value: LexerError::InvalidEscape { span: Span { start: 58, end: 61 } }, .. },
+        error @ Spanned { value: LexerError::InvalidUnicodeEscape { span: Span { start: 64, end: 72 } }, .. },
+        error @ Spanned { value: LexerError::InvalidUnicodeEscape { .. }, .. },
+        error @ Spanned { value: LexerError::InvalidUnicodeEscape { .. }, .. },
+        error @ Spanned { value: LexerError::InvalidUnicodeEscape { .. }, .. },
+        error @ Spanned { value: LexerError::InvalidUnicodeEscape { .. }, .. } => r#""\u{41""#,
+        error @ Spanned { value: LexerError::InvalidUnicodeEscape { span: Span { start: 117, end: 119 } }, .. },
+        error @ Spanned { value: LexerError::InvalidEscape { span: Span { start: 132, end: 134 } }, .. } => r#""\e "#,
+        error @ Spanned { value: LexerError::InvalidEscape { span: Span { start: 135, end: 137 } }, .. } => r#"\x""#,
+        kind @ TokenKind::Identifier => "ok"
+    );
+    test_lex!(
+        every_invalid_escape,
+        r#""\q\z" b"é\q" x"#,
+        error @ Spanned { value: LexerError::InvalidEscape { span: Span { start: 1, end: 3 } }, span: Span { start: 0, end: 3 } },
+        error @ Spanned { value: LexerError::InvalidEscape { span: Span { start: 3, end: 5 } }, span: Span { start: 3, end: 6 } },
+        error @ Spanned { value: LexerError::NonAsciiByte { span: Span { start: 9, end: 11 } }, .. } => r#"b"é"#,
+        error @ Spanned { value: LexerError::InvalidEscape { span: Span { start: 11, end: 13 } }, .. } => r#"\q""#,
+        kind @ TokenKind::Identifier => "x"
+    );
+    test_lex!(
+        chars,
+        r"
+        'a' 'é' '\n' '\'' '\u{1F600}' b'a' b'\xFF' b'\''
+        '' 'ab' '\q' b'é' b'\u{41}'
+        ",
+        token @ Token { kind: TokenKind::Char, slice: "'a'", .. },
+        token @ Token { kind: TokenKind::Char, slice: "'é'", .. },
+        token @ Token { kind: TokenKind::Char, slice: r"'\n'", .. },
+        token @ Token { kind: TokenKind::Char, slice: r"'\''", .. },
+        token @ Token { kind: TokenKind::Char, slice: r"'\u{1F600}'", .. },
+        token @ Token { kind: TokenKind::Byte, slice: "b'a'", .. },
+        token @ Token { kind: TokenKind::Byte, slice: r"b'\xFF'", .. },
+        token @ Token { kind: TokenKind::Byte, slice: r"b'\''", .. },
+        error @ Spanned { value: LexerError::InvalidCharLiteral, .. } => "''",
+        error @ Spanned { value: LexerError::InvalidCharLiteral, .. } => "'ab'",
+        error @ Spanned { value: LexerError::InvalidEscape { .. }, .. } => r"'\q'",
+        error @ Spanned { value: LexerError::NonAsciiByte { .. }, .. } => "b'é'",
+        error @ Spanned { value: LexerError::InvalidEscape { .. }, .. } => r"b'\u{41}'"
+    );
+    test_lex!(
+        byte_strings,
+        r#"
+        b"bytes" b"\x00\xFF\"" b"" b"é" b"\u{41}" b
+        "#,
+        token @ Token { kind: TokenKind::ByteString, slice: r#"b"bytes""#, .. },
+        token @ Token { kind: TokenKind::ByteString, slice: r#"b"\x00\xFF\"""#, .. },
+        token @ Token { kind: TokenKind::ByteString, slice: r#"b"""#, .. },
+        error @ Spanned { value: LexerError::NonAsciiByte { span: Span { start: 29, end: 31 } }, .. },
+        error @ Spanned { value: LexerError::InvalidEscape { span: Span { start: 35, end: 37 } }, .. },
+        token @ Token { kind: TokenKind::Identifier, slice: "b", .. }
     );
     test_lex!(
         open_string,
         r#""this is an open string"#,
-        error,
-        token @ Token { kind: TokenKind::Identifier, slice: "this" },
-        token @ Token { kind: TokenKind::Identifier, slice: "is" },
-        token @ Token { kind: TokenKind::Identifier, slice: "an" },
-        token @ Token { kind: TokenKind::Identifier, slice: "open" },
-        token @ Token { kind: TokenKind::Identifier, slice: "string" }
+        error @ Spanned { value: LexerError::UnterminatedString { start: 0 }, .. } => r#""this is an open string"#
+    );
+    test_lex!(
+        open_string_recovery,
+        r#"
+        const a 'c 'd
+        const b 'e
+        const c "open \x
+        const d
+        "#,
+        kind @ TokenKind::Const,
+        kind @ TokenKind::Identifier => "a",
+        error @ Spanned { value: LexerError::InvalidCharLiteral, .. } => "'c '",
+        kind @ TokenKind::Identifier => "d",
+        kind @ TokenKind::Const,
+        kind @ TokenKind::Identifier => "b",
+        error @ Spanned { value: LexerError::UnterminatedChar { start: 30 }, .. } => "'e",
+        kind @ TokenKind::Const,
+        kind @ TokenKind::Identifier => "c",
+        error @ Spanned { value: LexerError::UnterminatedString { start: 49 }, .. } => r#""open \x"#,
+        kind @ TokenKind::Const,
+        kind @ TokenKind::Identifier => "d"
+    );
+    test_lex!(
+        open_char_ends_at_line,
+        "'e\nx 'f'\nb'g\r\ny",
+        error @ Spanned { value: LexerError::UnterminatedChar { start: 0 }, .. } => "'e",
+        kind @ TokenKind::Identifier => "x",
+        token @ Token { kind: TokenKind::Char, slice: "'f'", .. },
+        error @ Spanned { value: LexerError::UnterminatedChar { start: 9 }, .. } => "b'g",
+        kind @ TokenKind::Identifier => "y"
     );
     test_lex!(
         open_raw_string,
         r##"r#"this is a raw string without end""##,
-        error
+        error @ Spanned { value: LexerError::UnterminatedRawString { hashes: 1 }, .. } => r##"r#"this is a raw string without end""##
+    );
+    test_lex!(
+        raw_string_terminators,
+        r####"
+        r"plain " r"" r##"with "# and "#""## r"é"
+        "####,
+        kind @ TokenKind::RawString => r#"r"plain ""#,
+        kind @ TokenKind::RawString => r#"r"""#,
+        kind @ TokenKind::RawString => r####"r##"with "# and "#""##"####,
+        kind @ TokenKind::RawString => r#"r"é""#
+    );
+    test_lex!(
+        unexpected_chars,
+        "a § b // ünïcode\n/* é */ c",
+        kind @ TokenKind::Identifier => "a",
+        error @ Spanned { value: LexerError::UnexpectedChar { ch: '§', span: Span { start: 2, end: 4 } }, .. } => "§",
+        kind @ TokenKind::Identifier => "b",
+        kind @ TokenKind::Comment => "// ünïcode",
+        kind @ TokenKind::Comment => "/* é */",
+        kind @ TokenKind::Identifier => "c"
     );
     test_lex!(
         symbols,
         r"
         ( ) { } [ ]
         , : ;
-        ->
+        -> => $
         ",
         kind @ TokenKind::LParen => "(",
         kind @ TokenKind::RParen => ")",
@@ -335,6 +653,215 @@ mod tests {
         kind @ TokenKind::Comma => ",",
         kind @ TokenKind::Colon => ":",
         kind @ TokenKind::Semicolon => ";",
-        kind @ TokenKind::Arrow => "->"
+        kind @ TokenKind::Arrow => "->",
+        kind @ TokenKind::FatArrow => "=>",
+        kind @ TokenKind::Dollar => "$"
     );
+    test_lex!(
+        spans,
+        "pub foo\n  (->) . \"é\"",
+        token @ Token { kind: TokenKind::Pub, span: Span { start: 0, end: 3 }, .. },
+        token @ Token { kind: TokenKind::Identifier, span: Span { start: 4, end: 7 }, .. },
+        token @ Token { kind: TokenKind::LParen, span: Span { start: 10, end: 11 }, .. },
+        token @ Token { kind: TokenKind::Arrow, span: Span { start: 11, end: 13 }, .. },
+        token @ Token { kind: TokenKind::RParen, span: Span { start: 13, end: 14 }, .. },
+        error @ Spanned { span: Span { start: 15, end: 16 }, .. },
+        token @ Token { kind: TokenKind::String, span: Span { start: 17, end: 21 }, .. }
+    );
+
+    #[test]
+    fn peek() {
+        let mut lexer = Lexer::new("const static pub");
+        assert!(matches!(
+            lexer.peek(1),
+            Some(Ok(Token {
+                kind: TokenKind::Static,
+                ..
+            }))
+        ));
+        assert_eq!(lexer.peek_kind(0), Some(&TokenKind::Const));
+        assert_eq!(lexer.peek_kind(2), Some(&TokenKind::Pub));
+        assert_eq!(lexer.peek_kind(3), None);
+        assert_lex!(lexer, kind @ TokenKind::Const => "const");
+        assert_eq!(lexer.peek_kind(0), Some(&TokenKind::Static));
+        assert_eq!(lexer.peek_kind(1), Some(&TokenKind::Pub));
+        assert_lex!(lexer, kind @ TokenKind::Static => "static", kind @ TokenKind::Pub => "pub");
+        assert_eq!(lexer.peek(0), None);
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn peek_error() {
+        let mut lexer = Lexer::new("( . )");
+        assert_eq!(lexer.peek_kind(1), None);
+        assert!(matches!(lexer.peek(1), Some(Err(_))));
+        assert_eq!(lexer.peek_kind(2), Some(&TokenKind::RParen));
+        assert_lex!(lexer, kind @ TokenKind::LParen, error => ".", kind @ TokenKind::RParen);
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn nth() {
+        let mut lexer = Lexer::new("( ) { } [ ]");
+        assert_eq!(lexer.peek_kind(1), Some(&TokenKind::RParen));
+        assert!(matches!(
+            lexer.nth(3),
+            Some(Ok(Token {
+                kind: TokenKind::RBrace,
+                ..
+            }))
+        ));
+        assert_eq!(lexer.slice(), "}");
+        assert!(matches!(
+            lexer.nth(1),
+            Some(Ok(Token {
+                kind: TokenKind::RBracket,
+                ..
+            }))
+        ));
+        assert_eq!(lexer.nth(1), None);
+    }
+
+    #[test]
+    fn checkpoint() {
+        let mut lexer = Lexer::new("const static pub");
+        assert_lex!(lexer, kind @ TokenKind::Const);
+        assert_eq!(lexer.peek_kind(0), Some(&TokenKind::Static));
+        let checkpoint = lexer.checkpoint();
+        assert_lex!(lexer, kind @ TokenKind::Static, kind @ TokenKind::Pub);
+        assert_eq!(lexer.next(), None);
+        lexer.rewind(checkpoint);
+        assert_eq!(lexer.prev_span(), Span::new(0, 5));
+        assert_lex!(lexer, kind @ TokenKind::Static => "static", kind @ TokenKind::Pub => "pub");
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn operators() {
+        let mut operators = OperatorTable::new();
+        for spelling in ["+", "++", "+=", "."] {
+            operators.define(spelling, OperatorDef::postfix(0)).unwrap();
+        }
+        let mut lexer = Lexer::with_operators("a+++b +=+ c.d -> e - - ?! f", operators);
+        assert_lex!(
+            lexer,
+            kind @ TokenKind::Identifier => "a",
+            kind @ TokenKind::Operator(1) => "++",
+            kind @ TokenKind::Operator(0) => "+",
+            kind @ TokenKind::Identifier => "b",
+            kind @ TokenKind::Operator(2) => "+=",
+            kind @ TokenKind::Operator(0) => "+",
+            kind @ TokenKind::Identifier => "c",
+            kind @ TokenKind::Operator(3) => ".",
+            kind @ TokenKind::Identifier => "d",
+            kind @ TokenKind::Arrow => "->",
+            kind @ TokenKind::Identifier => "e",
+            error @ Spanned { value: LexerError::UnknownOperator, .. } => "-",
+        );
+        assert_eq!(lexer.define_operator("-", OperatorDef::prefix(0)), Ok(4));
+        assert_lex!(
+            lexer,
+            kind @ TokenKind::Operator(4) => "-",
+            error @ Spanned { value: LexerError::UnknownOperator, .. } => "?!",
+            kind @ TokenKind::Identifier => "f",
+        );
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn unknown_operators() {
+        let mut lexer = Lexer::with_operators("+-*/ <//comment\n-->", OperatorTable::new());
+        assert_lex!(
+            lexer,
+            error @ Spanned { value: LexerError::UnknownOperator, .. } => "+-*/",
+            error @ Spanned { value: LexerError::UnknownOperator, .. } => "<",
+            kind @ TokenKind::Comment => "//comment",
+            error @ Spanned { value: LexerError::UnknownOperator, .. } => "-",
+            kind @ TokenKind::Arrow => "->"
+        );
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn prelude_operators() {
+        let operators = OperatorTable::prelude();
+        let id = |spelling| TokenKind::Operator(operators.get(spelling).unwrap());
+        let mut lexer = Lexer::with_operators("a += -b<<=c != !d", operators.clone());
+        assert_eq!(lexer.nth(1).map(|t| t.unwrap().kind), Some(id("+=")));
+        assert_eq!(lexer.next().map(|t| t.unwrap().kind), Some(id("-")));
+        assert_eq!(lexer.nth(1).map(|t| t.unwrap().kind), Some(id("<<=")));
+        assert_eq!(lexer.nth(1).map(|t| t.unwrap().kind), Some(id("!=")));
+        assert_eq!(lexer.next().map(|t| t.unwrap().kind), Some(id("!")));
+        assert_lex!(lexer, kind @ TokenKind::Identifier => "d");
+        assert_eq!(lexer.next(), None);
+    }
+
+    test_lex!(
+        trivia_skipped,
+        "a \t\r\n\n// comment\r\n\x0cb",
+        kind @ TokenKind::Identifier => "a",
+        kind @ TokenKind::Comment => "// comment",
+        kind @ TokenKind::Identifier => "b"
+    );
+
+    #[test]
+    fn trivia() {
+        let mut lexer = Lexer::with_trivia("a \t\r\n\n// comment\r\n\x0cb\r");
+        assert_lex!(
+            lexer,
+            kind @ TokenKind::Identifier => "a",
+            kind @ TokenKind::Whitespace => " \t",
+            kind @ TokenKind::Newline => "\r\n",
+            kind @ TokenKind::Newline => "\n",
+            kind @ TokenKind::Comment => "// comment",
+            kind @ TokenKind::Newline => "\r\n",
+            kind @ TokenKind::Whitespace => "\x0c",
+            kind @ TokenKind::Identifier => "b",
+            kind @ TokenKind::Whitespace => "\r",
+        );
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn trivia_lossless() {
+        let source = r##"
+            pub const x: i32 = 0x_1 + "str\"" "\q\z" b"é\q" ; /* block
+            /* nested */ */ ?? r#"raw"# ->
+            // trailing
+        "##;
+        let mut output = String::new();
+        for result in Lexer::with_trivia(source) {
+            let span = match result {
+                Ok(token) => token.span,
+                Err(error) => error.span,
+            };
+            output.push_str(&source[span.range()]);
+        }
+        assert_eq!(output, source);
+    }
+
+    #[test]
+    fn literal_values() {
+        let mut lexer = Lexer::new(r##"0xF_F 7u8 2f32 1_000 2.5e1_0 1. "a\tb" r#"c\td"# foo"##);
+        let mut next = || lexer.next().unwrap().unwrap();
+        assert_eq!(next().int_value(), Ok(0xFF));
+        let token = next();
+        assert_eq!(token.suffix(), Some(NumberSuffix::Uint(8)));
+        assert_eq!(token.int_value(), Ok(7));
+        let token = next();
+        assert!(token.int_value().is_err());
+        assert_eq!(token.float_value(), Ok(2.0));
+        assert_eq!(next().int_value(), Ok(1000));
+        assert_eq!(next().float_value(), Ok(2.5e10));
+        assert_eq!(next().float_value(), Ok(1.0));
+        assert_eq!(next().string_value().as_deref(), Ok("a\tb"));
+        assert_eq!(next().string_value().as_deref(), Ok("c\\td"));
+        assert!(matches!(
+            next().int_value(),
+            Err(LiteralError::UnexpectedKind {
+                found: TokenKind::Identifier,
+                ..
+            })
+        ));
+    }
 }
@@ -0,0 +1,200 @@
+use crate::span::Span;
+
+/// The unit in which columns are counted.
+///
+/// Terminal diagnostics usually want [`Encoding::Utf32`] (one column per character), while the
+/// Language Server Protocol counts [`Encoding::Utf16`] code units by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// Columns are counted in UTF-8 bytes.
+    #[default]
+    Utf8,
+    /// Columns are counted in UTF-16 code units.
+    Utf16,
+    /// Columns are counted in Unicode scalar values.
+    Utf32,
+}
+
+impl Encoding {
+    fn len_of(self, c: char) -> usize {
+        match self {
+            Encoding::Utf8 => c.len_utf8(),
+            Encoding::Utf16 => c.len_utf16(),
+            Encoding::Utf32 => 1,
+        }
+    }
+}
+
+/// A zero-based line and column pair.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl LineCol {
+    pub fn new(line: usize, col: usize) -> Self {
+        Self { line, col }
+    }
+}
+
+/// Maps byte offsets into a source to line/column positions and back.
+///
+/// Lines are terminated by `\n`; a preceding `\r` is considered part of the line contents.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'src> {
+    source: &'src str,
+    line_starts: Vec<usize>,
+}
+
+impl<'src> LineIndex<'src> {
+    pub fn new(source: &'src str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn source(&self) -> &'src str {
+        self.source
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the span of `line`, excluding its terminating `\n`.
+    pub fn line_span(&self, line: usize) -> Option<Span> {
+        let start = *self.line_starts.get(line)?;
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map_or(self.source.len(), |next| next - 1);
+        Some(Span::new(start, end))
+    }
+
+    /// Returns the text of `line`, excluding its terminating `\n`.
+    pub fn line(&self, line: usize) -> Option<&'src str> {
+        self.line_span(line).map(|span| &self.source[span.range()])
+    }
+
+    /// Converts a byte offset into a line/column pair, with the column measured in `encoding`.
+    ///
+    /// Returns `None` if `offset` is past the end of the source or not on a character boundary.
+    pub fn line_col(&self, offset: usize, encoding: Encoding) -> Option<LineCol> {
+        if !self.source.is_char_boundary(offset) {
+            return None;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let col = match encoding {
+            Encoding::Utf8 => offset - start,
+            _ => self.source[start..offset]
+                .chars()
+                .map(|c| encoding.len_of(c))
+                .sum(),
+        };
+        Some(LineCol { line, col })
+    }
+
+    /// Converts a line/column pair, with the column measured in `encoding`, back into a byte
+    /// offset.
+    ///
+    /// Returns `None` if the position lies outside its line or in the middle of a character.
+    pub fn offset(&self, pos: LineCol, encoding: Encoding) -> Option<usize> {
+        let span = self.line_span(pos.line)?;
+        if encoding == Encoding::Utf8 {
+            let offset = span.start + pos.col;
+            return (offset <= span.end && self.source.is_char_boundary(offset)).then_some(offset);
+        }
+
+        let mut col = 0;
+        for (i, c) in self.source[span.range()].char_indices() {
+            if col == pos.col {
+                return Some(span.start + i);
+            }
+            if col > pos.col {
+                return None;
+            }
+            col += encoding.len_of(c);
+        }
+        (col == pos.col).then_some(span.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoding, LineCol, LineIndex};
+
+    const SOURCE: &str = "const a\r\nstatic héllo 𝕏 = 1\n\nend";
+
+    #[test]
+    fn lines() {
+        let index = LineIndex::new(SOURCE);
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line(0), Some("const a\r"));
+        assert_eq!(index.line(1), Some("static héllo 𝕏 = 1"));
+        assert_eq!(index.line(2), Some(""));
+        assert_eq!(index.line(3), Some("end"));
+        assert_eq!(index.line(4), None);
+    }
+
+    #[test]
+    fn columns() {
+        let index = LineIndex::new(SOURCE);
+        let eq = SOURCE.find('=').unwrap();
+        assert_eq!(
+            index.line_col(eq, Encoding::Utf8),
+            Some(LineCol::new(1, 19))
+        );
+        assert_eq!(
+            index.line_col(eq, Encoding::Utf16),
+            Some(LineCol::new(1, 16))
+        );
+        assert_eq!(
+            index.line_col(eq, Encoding::Utf32),
+            Some(LineCol::new(1, 15))
+        );
+        assert_eq!(index.line_col(0, Encoding::Utf32), Some(LineCol::new(0, 0)));
+        assert_eq!(
+            index.line_col(SOURCE.len(), Encoding::Utf16),
+            Some(LineCol::new(3, 3))
+        );
+        assert_eq!(index.line_col(SOURCE.len() + 1, Encoding::Utf8), None);
+        assert_eq!(
+            index.line_col(SOURCE.find('é').unwrap() + 1, Encoding::Utf8),
+            None
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let index = LineIndex::new(SOURCE);
+        for encoding in [Encoding::Utf8, Encoding::Utf16, Encoding::Utf32] {
+            for (offset, _) in SOURCE.char_indices().chain([(SOURCE.len(), ' ')]) {
+                let pos = index.line_col(offset, encoding).unwrap();
+                assert_eq!(
+                    index.offset(pos, encoding),
+                    Some(offset),
+                    "{encoding:?} {pos:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_positions() {
+        let index = LineIndex::new(SOURCE);
+        let x = SOURCE.find('𝕏').unwrap();
+        let LineCol { line, col } = index.line_col(x, Encoding::Utf16).unwrap();
+        assert_eq!(
+            index.offset(LineCol::new(line, col + 1), Encoding::Utf16),
+            None
+        );
+        assert_eq!(index.offset(LineCol::new(2, 1), Encoding::Utf32), None);
+        assert_eq!(index.offset(LineCol::new(4, 0), Encoding::Utf8), None);
+    }
+}
@@ -0,0 +1,668 @@
+//! Decoding of literal tokens into the values they denote.
+
+use crate::token::{NumberSuffix, Token, TokenKind};
+use std::borrow::Cow;
+use thiserror::Error;
+
+/// An error decoding a literal. Offsets are in bytes, relative to the start of the token.
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum LiteralError {
+    #[error("expected {expected} literal, found {found:?}")]
+    UnexpectedKind {
+        expected: &'static str,
+        found: TokenKind,
+    },
+    #[error("invalid integer literal")]
+    InvalidInteger,
+    #[error("integer literal is too large")]
+    IntegerOverflow,
+    #[error("invalid float literal")]
+    InvalidFloat,
+    #[error("float literal is too large for `f{0}`")]
+    FloatOverflow(usize),
+    #[error("unsupported float width `f{0}`")]
+    UnsupportedFloatWidth(usize),
+    #[error("invalid escape sequence")]
+    InvalidEscape { offset: usize },
+    #[error("invalid unicode escape sequence")]
+    InvalidUnicodeEscape { offset: usize },
+    #[error("non-ASCII character in byte literal")]
+    NonAsciiByte { offset: usize },
+    #[error("character literals must contain exactly one character")]
+    InvalidCharLiteral,
+}
+
+impl LiteralError {
+    fn offset_by(self, by: usize) -> Self {
+        match self {
+            LiteralError::InvalidEscape { offset } => LiteralError::InvalidEscape {
+                offset: offset + by,
+            },
+            LiteralError::InvalidUnicodeEscape { offset } => LiteralError::InvalidUnicodeEscape {
+                offset: offset + by,
+            },
+            LiteralError::NonAsciiByte { offset } => LiteralError::NonAsciiByte {
+                offset: offset + by,
+            },
+            error => error,
+        }
+    }
+}
+
+/// Splits a numeric literal into its digits and type suffix, e.g. `255u8` into `("255", "u8")`.
+pub fn split_suffix(slice: &str) -> (&str, &str) {
+    // `f` is a hex digit, so a float suffix can only follow the exponent of a hex float
+    let (skip, float) = match slice.get(..2) {
+        Some("0x" | "0X") => slice.find(['p', 'P']).map_or((0, false), |p| (p, true)),
+        _ => (0, true),
+    };
+    let start = slice[skip..]
+        .find(|c| matches!(c, 'i' | 'u') || (c == 'f' && float))
+        .map_or(slice.len(), |start| skip + start);
+    slice.split_at(start)
+}
+
+/// Parses an integer literal, with an optional `0b`, `0o` or `0x` prefix and `_` separators.
+pub fn parse_int(slice: &str) -> Result<u128, LiteralError> {
+    let (radix, digits) = match slice.get(..2) {
+        Some("0b" | "0B") => (2, &slice[2..]),
+        Some("0o" | "0O") => (8, &slice[2..]),
+        Some("0x" | "0X") => (16, &slice[2..]),
+        _ => (10, slice),
+    };
+
+    let mut value: u128 = 0;
+    for c in digits.chars().filter(|&c| c != '_') {
+        let digit = c.to_digit(radix).ok_or(LiteralError::InvalidInteger)?;
+        value = value
+            .checked_mul(radix as u128)
+            .and_then(|value| value.checked_add(digit as u128))
+            .ok_or(LiteralError::IntegerOverflow)?;
+    }
+    Ok(value)
+}
+
+/// Parses a decimal float literal with `_` separators.
+pub fn parse_float(slice: &str) -> Result<f64, LiteralError> {
+    slice
+        .replace('_', "")
+        .parse::<f64>()
+        .map_err(|_| LiteralError::InvalidFloat)
+}
+
+/// Parses a hexadecimal float literal such as `0x1.8p3` into the bits of an IEEE 754 float of
+/// `width` bits, which must be 16, 32 or 64.
+///
+/// The value is rounded to the nearest representable float, ties to even, so every float of the
+/// given width can be written exactly.
+pub fn parse_hex_float(slice: &str, width: usize) -> Result<u64, LiteralError> {
+    parse_radix_float(slice, 4, width)
+}
+
+/// Parses a binary float literal such as `0b1.1p3`, like [`parse_hex_float`].
+pub fn parse_bin_float(slice: &str, width: usize) -> Result<u64, LiteralError> {
+    parse_radix_float(slice, 1, width)
+}
+
+/// Parses a float literal with a two-letter prefix and digits of `digit_bits` bits each.
+fn parse_radix_float(slice: &str, digit_bits: i64, width: usize) -> Result<u64, LiteralError> {
+    let (mantissa_bits, bias): (u32, i64) = match width {
+        16 => (10, 15),
+        32 => (23, 127),
+        64 => (52, 1023),
+        _ => return Err(LiteralError::UnsupportedFloatWidth(width)),
+    };
+
+    let slice = &slice[2..];
+    let (significand, exponent) = slice
+        .split_once(['p', 'P'])
+        .ok_or(LiteralError::InvalidFloat)?;
+
+    // value = mantissa * 2^exponent, with digits that don't fit collapsed into a sticky bit
+    let mut mantissa: u128 = 0;
+    let mut exponent = parse_exponent(exponent)?;
+    let mut sticky = false;
+    let mut fraction = false;
+    for c in significand.chars().filter(|&c| c != '_') {
+        if c == '.' {
+            fraction = true;
+            continue;
+        }
+        let digit = c
+            .to_digit(1 << digit_bits)
+            .ok_or(LiteralError::InvalidFloat)? as u128;
+        if mantissa >> (128 - digit_bits) == 0 {
+            mantissa = mantissa << digit_bits | digit;
+            exponent -= if fraction { digit_bits } else { 0 };
+        } else {
+            sticky |= digit != 0;
+            exponent += if fraction { 0 } else { digit_bits };
+        }
+    }
+    if mantissa == 0 {
+        return Ok(0);
+    }
+
+    // Pick the exponent of the least significant bit of the result, which is fixed for subnormals
+    let msb = 127 - mantissa.leading_zeros() as i64;
+    let min_exponent = 1 - bias - mantissa_bits as i64;
+    let mut lsb = (msb + exponent - mantissa_bits as i64).max(min_exponent);
+    let mut bits = round_shift(mantissa, lsb - exponent, sticky);
+    if bits >> (mantissa_bits + 1) != 0 {
+        bits >>= 1;
+        lsb += 1;
+    }
+
+    let implicit = 1 << mantissa_bits;
+    if bits < implicit {
+        return Ok(bits as u64);
+    }
+    let biased = lsb + mantissa_bits as i64 + bias;
+    if biased > 2 * bias {
+        return Err(LiteralError::FloatOverflow(width));
+    }
+    Ok(((biased as u64) << mantissa_bits) | (bits - implicit) as u64)
+}
+
+fn parse_exponent(exponent: &str) -> Result<i64, LiteralError> {
+    let (negative, digits) = match exponent.as_bytes().first() {
+        Some(b'-') => (true, &exponent[1..]),
+        Some(b'+') => (false, &exponent[1..]),
+        _ => (false, exponent),
+    };
+    let mut value: i64 = 0;
+    for c in digits.chars().filter(|&c| c != '_') {
+        let digit = c.to_digit(10).ok_or(LiteralError::InvalidFloat)? as i64;
+        // Anything past this is far out of range of every float width anyway
+        value = (value * 10 + digit).min(1 << 32);
+    }
+    Ok(if negative { -value } else { value })
+}
+
+/// Computes `value / 2^shift`, rounding to nearest, ties to even. `sticky` is set if `value` was
+/// already truncated.
+fn round_shift(value: u128, shift: i64, sticky: bool) -> u128 {
+    if shift <= 0 {
+        return value << -shift;
+    }
+    if shift > 128 {
+        return 0;
+    }
+    let half = (value >> (shift - 1)) & 1 == 1;
+    let below_half = value & ((1 << (shift - 1)) - 1) != 0 || sticky;
+    let truncated = if shift == 128 { 0 } else { value >> shift };
+    if half && (below_half || truncated & 1 == 1) {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+/// Converts the bits of a float of `width` bits, as returned by [`parse_hex_float`] and
+/// [`parse_bin_float`], to an `f64`.
+pub fn float_from_bits(bits: u64, width: usize) -> f64 {
+    match width {
+        16 => {
+            let sign = if bits >> 15 == 1 { -1.0 } else { 1.0 };
+            let exponent = (bits >> 10) & 0x1F;
+            let fraction = (bits & 0x3FF) as f64;
+            sign * match exponent {
+                0 => fraction * 2f64.powi(-24),
+                0x1F if fraction == 0.0 => f64::INFINITY,
+                0x1F => f64::NAN,
+                _ => (1024.0 + fraction) * 2f64.powi(exponent as i32 - 25),
+            }
+        }
+        32 => f32::from_bits(bits as u32) as f64,
+        _ => f64::from_bits(bits),
+    }
+}
+
+/// Resolves the escape sequences in the contents of a string literal.
+///
+/// Supported escapes are `\n`, `\r`, `\t`, `\\`, `\"`, `\'`, `\0`, `\xNN` for ASCII characters
+/// and `\u{N...}` for Unicode scalar values.
+pub fn unescape(content: &str) -> Result<Cow<'_, str>, LiteralError> {
+    if !content.contains('\\') {
+        return Ok(Cow::Borrowed(content));
+    }
+
+    let mut output = String::with_capacity(content.len());
+    unescape_with(content, false, |c| output.push(c))?;
+    Ok(Cow::Owned(output))
+}
+
+/// Resolves the escape sequences in the contents of a byte string literal.
+///
+/// The same escapes as [`unescape`] are supported, except for `\u{...}`, and `\xNN` can encode
+/// any byte. Unescaped characters must be ASCII.
+pub fn unescape_bytes(content: &str) -> Result<Cow<'_, [u8]>, LiteralError> {
+    if !content.contains('\\') {
+        return match content.bytes().position(|b| !b.is_ascii()) {
+            Some(offset) => Err(LiteralError::NonAsciiByte { offset }),
+            None => Ok(Cow::Borrowed(content.as_bytes())),
+        };
+    }
+
+    let mut output = Vec::with_capacity(content.len());
+    unescape_with(content, true, |c| output.push(c as u8))?;
+    Ok(Cow::Owned(output))
+}
+
+fn unescape_with(
+    content: &str,
+    bytes: bool,
+    mut push: impl FnMut(char),
+) -> Result<(), LiteralError> {
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        let offset = content.len() - chars.as_str().len() - c.len_utf8();
+        if c != '\\' {
+            if bytes && !c.is_ascii() {
+                return Err(LiteralError::NonAsciiByte { offset });
+            }
+            push(c);
+            continue;
+        }
+
+        let (escaped, len) =
+            unescape_one(chars.as_str(), bytes).map_err(|(error, _)| error.offset_by(offset))?;
+        push(escaped);
+        chars = content[offset + 1 + len..].chars();
+    }
+    Ok(())
+}
+
+/// Resolves the escape whose backslash precedes `rest`, returning the character and the number
+/// of bytes consumed from `rest`.
+///
+/// In `bytes` mode, `\xNN` can encode any byte and `\u{...}` is not allowed.
+///
+/// On failure, returns the error, with offsets relative to the backslash, and the number of bytes
+/// of `rest` making up the invalid escape.
+pub(crate) fn unescape_one(
+    rest: &str,
+    bytes: bool,
+) -> Result<(char, usize), (LiteralError, usize)> {
+    let invalid = LiteralError::InvalidEscape { offset: 0 };
+    let c = rest.chars().next().ok_or((invalid.clone(), 0))?;
+    match c {
+        'n' => Ok(('\n', 1)),
+        'r' => Ok(('\r', 1)),
+        't' => Ok(('\t', 1)),
+        '\\' => Ok(('\\', 1)),
+        '"' => Ok(('"', 1)),
+        '\'' => Ok(('\'', 1)),
+        '0' => Ok(('\0', 1)),
+        'x' => {
+            let hex = rest[1..]
+                .char_indices()
+                .take_while(|&(i, c)| i < 2 && c.is_ascii_hexdigit())
+                .count();
+            let value = u8::from_str_radix(&rest[1..1 + hex], 16)
+                .ok()
+                .filter(|value| hex == 2 && (bytes || value.is_ascii()))
+                .ok_or((invalid, 1 + hex))?;
+            Ok((value as char, 3))
+        }
+        'u' if !bytes => {
+            let invalid = LiteralError::InvalidUnicodeEscape { offset: 0 };
+            let Some(braced) = rest.strip_prefix("u{") else {
+                return Err((invalid, 1));
+            };
+            let digits = &braced[..braced.bytes().take_while(u8::is_ascii_hexdigit).count()];
+            if !braced[digits.len()..].starts_with('}') {
+                return Err((invalid, digits.len() + 2));
+            }
+            let len = digits.len() + 3;
+            if !(1..=6).contains(&digits.len()) {
+                return Err((invalid, len));
+            }
+            let c = u32::from_str_radix(digits, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or((invalid, len))?;
+            Ok((c, len))
+        }
+        c => Err((invalid, c.len_utf8())),
+    }
+}
+
+/// Parses a string literal, either `"..."` with escapes or a raw `r#"..."#`.
+pub fn parse_string(slice: &str) -> Result<Cow<'_, str>, LiteralError> {
+    if let Some(raw) = slice.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return Ok(Cow::Borrowed(&raw[hashes + 1..raw.len() - hashes - 1]));
+    }
+    unescape(&slice[1..slice.len() - 1]).map_err(|e| e.offset_by(1))
+}
+
+/// Parses a character literal `'c'`.
+pub fn parse_char(slice: &str) -> Result<char, LiteralError> {
+    let content = unescape(&slice[1..slice.len() - 1]).map_err(|e| e.offset_by(1))?;
+    single(content.chars())
+}
+
+/// Parses a byte literal `b'c'`.
+pub fn parse_byte(slice: &str) -> Result<u8, LiteralError> {
+    let bytes = unescape_bytes(&slice[2..slice.len() - 1]).map_err(|e| e.offset_by(2))?;
+    single(bytes.iter().copied())
+}
+
+/// Parses a byte string literal `b"..."`.
+pub fn parse_byte_string(slice: &str) -> Result<Cow<'_, [u8]>, LiteralError> {
+    unescape_bytes(&slice[2..slice.len() - 1]).map_err(|e| e.offset_by(2))
+}
+
+fn single<T>(mut values: impl Iterator<Item = T>) -> Result<T, LiteralError> {
+    match (values.next(), values.next()) {
+        (Some(value), None) => Ok(value),
+        _ => Err(LiteralError::InvalidCharLiteral),
+    }
+}
+
+impl<'src> Token<'src> {
+    /// Returns the type suffix of a numeric literal.
+    pub fn suffix(&self) -> Option<NumberSuffix> {
+        match self.kind {
+            TokenKind::DecInt(suffix)
+            | TokenKind::BinInt(suffix)
+            | TokenKind::OctInt(suffix)
+            | TokenKind::HexInt(suffix)
+            | TokenKind::Float(suffix)
+            | TokenKind::FloatExp(suffix)
+            | TokenKind::IntExp(suffix)
+            | TokenKind::HexFloat(suffix)
+            | TokenKind::BinFloat(suffix) => suffix,
+            _ => None,
+        }
+    }
+
+    /// Decodes a [`TokenKind::DecInt`], [`TokenKind::BinInt`], [`TokenKind::OctInt`] or
+    /// [`TokenKind::HexInt`] literal without a float suffix.
+    pub fn int_value(&self) -> Result<u128, LiteralError> {
+        match self.kind {
+            TokenKind::DecInt(suffix)
+            | TokenKind::BinInt(suffix)
+            | TokenKind::OctInt(suffix)
+            | TokenKind::HexInt(suffix)
+                if !matches!(suffix, Some(NumberSuffix::Float(_))) =>
+            {
+                parse_int(split_suffix(self.slice).0)
+            }
+            _ => Err(self.unexpected_kind("integer")),
+        }
+    }
+
+    /// Decodes a [`TokenKind::Float`], [`TokenKind::IntFloat`], [`TokenKind::FloatExp`],
+    /// [`TokenKind::IntExp`], [`TokenKind::HexFloat`] or [`TokenKind::BinFloat`] literal, or a
+    /// [`TokenKind::DecInt`] with a float suffix.
+    pub fn float_value(&self) -> Result<f64, LiteralError> {
+        match self.kind {
+            TokenKind::Float(_)
+            | TokenKind::IntFloat
+            | TokenKind::FloatExp(_)
+            | TokenKind::IntExp(_)
+            | TokenKind::DecInt(Some(NumberSuffix::Float(_))) => {
+                parse_float(split_suffix(self.slice).0)
+            }
+            TokenKind::HexFloat(suffix) => {
+                let bits = self.hex_float_bits()?;
+                Ok(float_from_bits(bits, float_width(suffix)))
+            }
+            TokenKind::BinFloat(suffix) => {
+                let bits = self.bin_float_bits()?;
+                Ok(float_from_bits(bits, float_width(suffix)))
+            }
+            _ => Err(self.unexpected_kind("float")),
+        }
+    }
+
+    /// Decodes a [`TokenKind::HexFloat`] literal into the bits of a float of its suffix width, or
+    /// an `f64` if it has none.
+    pub fn hex_float_bits(&self) -> Result<u64, LiteralError> {
+        match self.kind {
+            TokenKind::HexFloat(suffix) => {
+                parse_hex_float(split_suffix(self.slice).0, float_width(suffix))
+            }
+            _ => Err(self.unexpected_kind("hexadecimal float")),
+        }
+    }
+
+    /// Decodes a [`TokenKind::BinFloat`] literal into the bits of a float of its suffix width, or
+    /// an `f64` if it has none.
+    pub fn bin_float_bits(&self) -> Result<u64, LiteralError> {
+        match self.kind {
+            TokenKind::BinFloat(suffix) => {
+                parse_bin_float(split_suffix(self.slice).0, float_width(suffix))
+            }
+            _ => Err(self.unexpected_kind("binary float")),
+        }
+    }
+
+    /// Decodes a [`TokenKind::String`] or [`TokenKind::RawString`] literal, borrowing from the
+    /// source unless it contains escapes.
+    pub fn string_value(&self) -> Result<Cow<'src, str>, LiteralError> {
+        match self.kind {
+            TokenKind::String | TokenKind::RawString => parse_string(self.slice),
+            _ => Err(self.unexpected_kind("string")),
+        }
+    }
+
+    /// Decodes a [`TokenKind::Char`] literal.
+    pub fn char_value(&self) -> Result<char, LiteralError> {
+        match self.kind {
+            TokenKind::Char => parse_char(self.slice),
+            _ => Err(self.unexpected_kind("character")),
+        }
+    }
+
+    /// Decodes a [`TokenKind::Byte`] literal.
+    pub fn byte_value(&self) -> Result<u8, LiteralError> {
+        match self.kind {
+            TokenKind::Byte => parse_byte(self.slice),
+            _ => Err(self.unexpected_kind("byte")),
+        }
+    }
+
+    /// Decodes a [`TokenKind::ByteString`] literal, borrowing from the source unless it contains
+    /// escapes.
+    pub fn byte_string_value(&self) -> Result<Cow<'src, [u8]>, LiteralError> {
+        match self.kind {
+            TokenKind::ByteString => parse_byte_string(self.slice),
+            _ => Err(self.unexpected_kind("byte string")),
+        }
+    }
+
+    fn unexpected_kind(&self, expected: &'static str) -> LiteralError {
+        LiteralError::UnexpectedKind {
+            expected,
+            found: self.kind.clone(),
+        }
+    }
+}
+
+fn float_width(suffix: Option<NumberSuffix>) -> usize {
+    match suffix {
+        Some(NumberSuffix::Float(width)) => width,
+        _ => 64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        LiteralError, float_from_bits, parse_bin_float, parse_byte, parse_byte_string, parse_char,
+        parse_float, parse_hex_float, parse_int, parse_string, split_suffix, unescape,
+    };
+
+    #[test]
+    fn ints() {
+        assert_eq!(parse_int("1_2__3"), Ok(123));
+        assert_eq!(parse_int("0b10_10"), Ok(0b1010));
+        assert_eq!(parse_int("0O7_755"), Ok(0o7755));
+        assert_eq!(parse_int("0xaA_55"), Ok(0xAA55));
+        assert_eq!(
+            parse_int("340282366920938463463374607431768211455"),
+            Ok(u128::MAX)
+        );
+        assert_eq!(
+            parse_int("340282366920938463463374607431768211456"),
+            Err(LiteralError::IntegerOverflow)
+        );
+        assert_eq!(parse_int("0b102"), Err(LiteralError::InvalidInteger));
+    }
+
+    #[test]
+    fn suffixes() {
+        assert_eq!(split_suffix("255u8"), ("255", "u8"));
+        assert_eq!(split_suffix("1_0isize"), ("1_0", "isize"));
+        assert_eq!(split_suffix("0xFFi32"), ("0xFF", "i32"));
+        assert_eq!(split_suffix("0x1f32"), ("0x1f32", ""));
+        assert_eq!(split_suffix("1.5e3f64"), ("1.5e3", "f64"));
+        assert_eq!(split_suffix("42"), ("42", ""));
+        assert_eq!(split_suffix("0x1.fp-1f32"), ("0x1.fp-1", "f32"));
+        assert_eq!(split_suffix("0xfp1"), ("0xfp1", ""));
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(parse_float("1_0.5"), Ok(10.5));
+        assert_eq!(parse_float("1."), Ok(1.0));
+        assert_eq!(parse_float("1.5E-1_0"), Ok(1.5e-10));
+        assert_eq!(parse_float("2e+3"), Ok(2e3));
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(parse_string(r#""plain""#).unwrap(), "plain");
+        assert_eq!(
+            parse_string(r#""\n\r\t\\\"\'\0\x41\u{1F600}\u{e9}""#).unwrap(),
+            "\n\r\t\\\"'\0A😀é"
+        );
+        assert_eq!(parse_string(r##"r#"raw \n "#"##).unwrap(), r#"raw \n "#);
+        assert_eq!(parse_string(r#"r"raw""#).unwrap(), "raw");
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        assert_eq!(
+            parse_string(r#""ab\q""#),
+            Err(LiteralError::InvalidEscape { offset: 3 })
+        );
+        assert_eq!(
+            unescape(r"é\x80"),
+            Err(LiteralError::InvalidEscape { offset: 2 })
+        );
+        assert_eq!(
+            unescape(r"\x4"),
+            Err(LiteralError::InvalidEscape { offset: 0 })
+        );
+        assert_eq!(
+            unescape(r"ok\u{D800}"),
+            Err(LiteralError::InvalidUnicodeEscape { offset: 2 })
+        );
+        assert_eq!(
+            unescape(r"\u{1234567}"),
+            Err(LiteralError::InvalidUnicodeEscape { offset: 0 })
+        );
+        assert_eq!(
+            unescape(r"\x+4"),
+            Err(LiteralError::InvalidEscape { offset: 0 })
+        );
+        assert_eq!(
+            unescape(r"\u41"),
+            Err(LiteralError::InvalidUnicodeEscape { offset: 0 })
+        );
+        assert_eq!(
+            unescape(r"\u{41"),
+            Err(LiteralError::InvalidUnicodeEscape { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn chars_and_bytes() {
+        assert_eq!(parse_char("'a'"), Ok('a'));
+        assert_eq!(parse_char("'é'"), Ok('é'));
+        assert_eq!(parse_char(r"'\u{1F600}'"), Ok('😀'));
+        assert_eq!(parse_char("'ab'"), Err(LiteralError::InvalidCharLiteral));
+        assert_eq!(parse_char("''"), Err(LiteralError::InvalidCharLiteral));
+        assert_eq!(parse_byte(r"b'\xFF'"), Ok(0xFF));
+        assert_eq!(parse_byte("b'\n'"), Ok(b'\n'));
+        assert_eq!(
+            parse_byte("b'é'"),
+            Err(LiteralError::NonAsciiByte { offset: 2 })
+        );
+        assert_eq!(
+            parse_byte_string(r#"b"a\x00\xFF\"""#).as_deref(),
+            Ok(&b"a\x00\xFF\""[..])
+        );
+        assert_eq!(
+            parse_byte_string(r#"b"\u{41}""#),
+            Err(LiteralError::InvalidEscape { offset: 2 })
+        );
+    }
+
+    #[test]
+    fn hex_floats() {
+        let f64_bits = |slice| parse_hex_float(slice, 64);
+        let f32_bits = |slice| parse_hex_float(slice, 32).map(|bits| bits as u32);
+        assert_eq!(f64_bits("0x1.8p3"), Ok(12f64.to_bits()));
+        assert_eq!(f64_bits("0x.8p1"), Ok(1f64.to_bits()));
+        assert_eq!(f64_bits("0xA_Bp-4"), Ok(10.6875f64.to_bits()));
+        assert_eq!(f64_bits("0x0p0"), Ok(0));
+        assert_eq!(f64_bits("0x1.fffffffffffffp1023"), Ok(f64::MAX.to_bits()));
+        assert_eq!(f64_bits("0x1p-1022"), Ok(f64::MIN_POSITIVE.to_bits()));
+        assert_eq!(f64_bits("0x1p-1074"), Ok(1));
+        assert_eq!(f64_bits("0x1p-1075"), Ok(0));
+        assert_eq!(f64_bits("0x1.8p-1075"), Ok(1));
+        assert_eq!(
+            f64_bits("0x1.0000000000000000000000000000000000001p0"),
+            Ok(1f64.to_bits())
+        );
+        assert_eq!(
+            f64_bits("0x1.fffffffffffff8p1023"),
+            Err(LiteralError::FloatOverflow(64))
+        );
+        assert_eq!(
+            f64_bits("0x1p99999999999"),
+            Err(LiteralError::FloatOverflow(64))
+        );
+
+        assert_eq!(f32_bits("0x1.fffffep127"), Ok(f32::MAX.to_bits()));
+        assert_eq!(f32_bits("0x1p-149"), Ok(1));
+        assert_eq!(f32_bits("0x1.000001p0"), Ok(1f32.to_bits()));
+        assert_eq!(f32_bits("0x1.000003p0"), Ok(0x3f80_0002));
+        assert_eq!(f32_bits("0x1.fffffffp0"), Ok(2f32.to_bits()));
+        assert_eq!(f32_bits("0x1p128"), Err(LiteralError::FloatOverflow(32)));
+
+        assert_eq!(parse_hex_float("0x1.ffcp15", 16), Ok(0x7bff));
+        assert_eq!(parse_hex_float("0x1p-24", 16), Ok(1));
+        assert_eq!(
+            parse_hex_float("0x1.ffep15", 16),
+            Err(LiteralError::FloatOverflow(16))
+        );
+        assert_eq!(
+            parse_hex_float("0x1p0", 80),
+            Err(LiteralError::UnsupportedFloatWidth(80))
+        );
+
+        assert_eq!(parse_bin_float("0b1.1p3", 64), Ok(12f64.to_bits()));
+        assert_eq!(parse_bin_float("0b.1p1", 64), Ok(1f64.to_bits()));
+        assert_eq!(parse_bin_float("0b1_0p-1", 32), Ok(1f32.to_bits() as u64));
+        assert_eq!(parse_bin_float("0b1p-24", 16), Ok(1));
+        assert_eq!(
+            parse_bin_float("0b1.000000000000000000000001p0", 32),
+            Ok(1f32.to_bits() as u64)
+        );
+        assert_eq!(
+            parse_bin_float("0b1p128", 32),
+            Err(LiteralError::FloatOverflow(32))
+        );
+
+        assert_eq!(float_from_bits(0x7bff, 16), 65504.0);
+        assert_eq!(float_from_bits(1, 16), 2f64.powi(-24));
+        assert_eq!(float_from_bits(0xc000, 16), -2.0);
+        assert_eq!(float_from_bits(1.5f32.to_bits() as u64, 32), 1.5);
+    }
+}
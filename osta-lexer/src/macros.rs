@@ -0,0 +1,988 @@
+//! Declarative macros, expanded at the token level.
+//!
+//! Macros are defined in the source with `@macro_rules` and a list of rules, each a pattern and
+//! the tokens it expands to:
+//!
+//! ```text
+//! @macro_rules max {
+//!     ($a:tt) => { $a };
+//!     ($a:tt, $($rest:tt),+) => { @max_of($a, @max($($rest),+)) };
+//! }
+//! ```
+//!
+//! Patterns match the token trees inside the invocation's delimiters. A metavariable `$name:frag`
+//! matches an `ident`ifier, a `literal` or any single token tree (`tt`), and `$( ... ) sep op`
+//! repeats its contents, separated by the optional token `sep`, zero or more (`*`), one or more
+//! (`+`) or at most one (`?`) times. Matching is greedy and never backtracks into a repetition.
+//!
+//! Invocations of macros defined earlier in the stream, like `@max(a, b, c)`, are replaced by
+//! the expansion of the first rule that matches them. Other macro identifiers are passed through.
+
+use crate::directive::FileId;
+use crate::lexer::{LexerError, TokenResult};
+use crate::operator::OperatorTable;
+use crate::span::{Span, Spanned};
+use crate::token::{Token, TokenKind};
+use crate::token_tree::{Delimiter, Group, TokenTree, parse_group};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// How deeply expansions may nest before expanding is abandoned.
+pub const RECURSION_LIMIT: usize = 64;
+
+/// Identifies the macro expansion a token was written in, [`ExpansionId::ROOT`] being the
+/// source itself.
+///
+/// This doubles as the hygiene mark of identifiers: tokens written in a macro's rules belong to
+/// a fresh expansion, while tokens passed as arguments keep the expansion of the call site, so
+/// two identifiers refer to the same binding only if both their spelling and expansion match.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExpansionId(u32);
+
+impl ExpansionId {
+    pub const ROOT: ExpansionId = ExpansionId(0);
+
+    pub fn is_root(self) -> bool {
+        self == Self::ROOT
+    }
+}
+
+/// Where a macro expansion came from, used to point errors in expanded code back at the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expansion {
+    /// The name of the expanded macro, without the `@`.
+    pub name: String,
+    /// The invocation, from the macro identifier to the closing delimiter.
+    pub call_site: Span,
+    /// The `@macro_rules` definition of the macro.
+    pub def_site: Span,
+    /// The expansion the invocation was written in.
+    pub parent: ExpansionId,
+}
+
+/// A macro defined with `@macro_rules`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroDef<'src> {
+    pub name: &'src str,
+    pub span: Span,
+    rules: Vec<Rule<'src>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule<'src> {
+    pattern: Vec<MacroTree<'src>>,
+    body: Vec<MacroTree<'src>>,
+}
+
+/// The parsed form of macro patterns and bodies.
+#[derive(Debug, Clone, PartialEq)]
+enum MacroTree<'src> {
+    Token(Token<'src>),
+    Group {
+        delimiter: Delimiter,
+        open: Span,
+        close: Span,
+        file: FileId,
+        trees: Vec<MacroTree<'src>>,
+    },
+    /// `$name:fragment` in patterns, or `$name` in bodies.
+    Var {
+        token: Token<'src>,
+        fragment: Option<Fragment>,
+    },
+    Repeat {
+        trees: Vec<MacroTree<'src>>,
+        separator: Option<Token<'src>>,
+        op: RepeatOp,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fragment {
+    Ident,
+    Literal,
+    Tt,
+}
+
+impl Fragment {
+    fn matches(self, tree: &TokenTree) -> bool {
+        match (self, tree) {
+            (Fragment::Tt, _) => true,
+            (Fragment::Ident, TokenTree::Token(token)) => token.kind == TokenKind::Identifier,
+            (Fragment::Literal, TokenTree::Token(token)) => matches!(
+                token.kind,
+                TokenKind::DecInt(_)
+                    | TokenKind::BinInt(_)
+                    | TokenKind::OctInt(_)
+                    | TokenKind::HexInt(_)
+                    | TokenKind::Float(_)
+                    | TokenKind::IntFloat
+                    | TokenKind::FloatExp(_)
+                    | TokenKind::IntExp(_)
+                    | TokenKind::HexFloat(_)
+                    | TokenKind::BinFloat(_)
+                    | TokenKind::String
+                    | TokenKind::ByteString
+                    | TokenKind::RawString
+                    | TokenKind::Char
+                    | TokenKind::Byte
+            ),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RepeatOp {
+    ZeroOrMore,
+    OneOrMore,
+    ZeroOrOne,
+}
+
+impl RepeatOp {
+    /// Returns the operator `text` starts with, if any.
+    fn starting(text: &str) -> Option<Self> {
+        match text.chars().next()? {
+            '*' => Some(RepeatOp::ZeroOrMore),
+            '+' => Some(RepeatOp::OneOrMore),
+            '?' => Some(RepeatOp::ZeroOrOne),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Binding<'src> {
+    One(TokenTree<'src>),
+    Many(Vec<Binding<'src>>),
+}
+
+type Bindings<'src> = HashMap<&'src str, Binding<'src>>;
+
+/// The macro state of a [`Lexer`](crate::Lexer): defined macros, recorded expansions and the
+/// expanded tokens waiting to be returned.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Expander<'src> {
+    macros: Arc<HashMap<&'src str, Arc<MacroDef<'src>>>>,
+    expansions: Arc<Vec<Expansion>>,
+    pending: VecDeque<TokenResult<'src>>,
+}
+
+impl<'src> Expander<'src> {
+    pub(crate) fn expansion(&self, id: ExpansionId) -> Option<&Expansion> {
+        let index = (id.0 as usize).checked_sub(1)?;
+        self.expansions.get(index)
+    }
+
+    /// Exchanges the defined macros and recorded expansions with `other`, keeping the pending
+    /// tokens of each.
+    pub(crate) fn swap_definitions(&mut self, other: &mut Expander<'src>) {
+        std::mem::swap(&mut self.macros, &mut other.macros);
+        std::mem::swap(&mut self.expansions, &mut other.expansions);
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&MacroDef<'src>> {
+        self.macros.get(name).map(|def| &**def)
+    }
+
+    /// Returns the next token with macros expanded, reading unexpanded tokens of `source` from
+    /// `raw`, whose runs of operator characters are split with `operators`.
+    pub(crate) fn next(
+        &mut self,
+        source: &'src str,
+        operators: &OperatorTable,
+        raw: &mut impl FnMut() -> Option<TokenResult<'src>>,
+    ) -> Option<TokenResult<'src>> {
+        loop {
+            let token = match self.read(raw)? {
+                Ok(token) if token.kind == TokenKind::MacroIdentifier => token,
+                result => return Some(result),
+            };
+
+            if token.slice == "@macro_rules" {
+                self.define(token, source, operators, raw);
+            } else if let Some(def) = self.macros.get(&token.slice[1..]).cloned() {
+                self.expand(token, &def, raw);
+            } else {
+                return Some(Ok(token));
+            }
+        }
+    }
+
+    /// Returns the next token already expanded or read ahead, if any.
+    pub(crate) fn next_pending(&mut self) -> Option<TokenResult<'src>> {
+        self.pending.pop_front()
+    }
+
+    fn read(
+        &mut self,
+        raw: &mut impl FnMut() -> Option<TokenResult<'src>>,
+    ) -> Option<TokenResult<'src>> {
+        self.pending.pop_front().or_else(raw)
+    }
+
+    /// Queues `results` to be returned before anything else.
+    fn unread(&mut self, results: impl DoubleEndedIterator<Item = TokenResult<'src>>) {
+        for result in results.rev() {
+            self.pending.push_front(result);
+        }
+    }
+
+    /// Reads the group following a macro identifier, if there is one with `delimiter`.
+    fn read_group(
+        &mut self,
+        raw: &mut impl FnMut() -> Option<TokenResult<'src>>,
+        delimiter: Option<Delimiter>,
+    ) -> Option<(Group<'src>, Vec<Spanned<LexerError>>)> {
+        let open = match self.read(raw) {
+            Some(Ok(token))
+                if Delimiter::from_open(&token.kind)
+                    .is_some_and(|found| delimiter.is_none_or(|expected| found == expected)) =>
+            {
+                token
+            }
+            result => {
+                self.unread(result.into_iter());
+                return None;
+            }
+        };
+        Some(parse_group(open, || self.read(raw)))
+    }
+
+    fn define(
+        &mut self,
+        token: Token<'src>,
+        source: &'src str,
+        operators: &OperatorTable,
+        raw: &mut impl FnMut() -> Option<TokenResult<'src>>,
+    ) {
+        let invalid = |reason, span| {
+            Err(Spanned::new(
+                LexerError::InvalidMacroDefinition { reason },
+                span,
+            ))
+        };
+
+        let name = match self.read(raw) {
+            Some(Ok(name)) if name.kind == TokenKind::Identifier => name,
+            result => {
+                self.unread(result.into_iter());
+                self.unread([invalid("expected a macro name", token.span)].into_iter());
+                return;
+            }
+        };
+        let Some((group, errors)) = self.read_group(raw, Some(Delimiter::Brace)) else {
+            let span = token.span.to(name.span);
+            self.unread([invalid("expected `{` after the macro name", span)].into_iter());
+            return;
+        };
+
+        let span = token.span.to(group.close);
+        let mut parser = DefParser {
+            source,
+            operators,
+            errors,
+        };
+        let parsed = parser.rules(&group.trees);
+        let mut results: Vec<_> = parser.errors.into_iter().map(Err).collect();
+        match parsed {
+            Ok(rules) => {
+                let def = MacroDef {
+                    name: name.slice,
+                    span,
+                    rules,
+                };
+                Arc::make_mut(&mut self.macros).insert(name.slice, Arc::new(def));
+            }
+            Err((reason, span)) => results.push(invalid(reason, span)),
+        }
+        self.unread(results.into_iter());
+    }
+
+    fn expand(
+        &mut self,
+        token: Token<'src>,
+        def: &MacroDef<'src>,
+        raw: &mut impl FnMut() -> Option<TokenResult<'src>>,
+    ) {
+        let error = |error, span| Err(Spanned::new(error, span));
+        let name = || def.name.to_string();
+
+        let Some((group, errors)) = self.read_group(raw, None) else {
+            let missing = LexerError::MissingMacroArguments { name: name() };
+            self.unread([error(missing, token.span)].into_iter());
+            return;
+        };
+        let call_site = token.span.to(group.close);
+        let mut results: Vec<_> = errors.into_iter().map(Err).collect();
+
+        let depth = std::iter::successors(self.expansion(token.expansion), |expansion| {
+            self.expansion(expansion.parent)
+        })
+        .count();
+        if depth >= RECURSION_LIMIT {
+            let limit = LexerError::MacroRecursionLimit { name: name() };
+            results.push(error(limit, call_site));
+            self.unread(results.into_iter());
+            return;
+        }
+
+        let matched = def.rules.iter().find_map(|rule| {
+            let mut bindings = Bindings::new();
+            let mut pos = 0;
+            let matched = match_trees(&rule.pattern, &group.trees, &mut pos, &mut bindings);
+            (matched && pos == group.trees.len()).then_some((rule, bindings))
+        });
+        let Some((rule, bindings)) = matched else {
+            let unmatched = LexerError::NoMatchingMacroRule { name: name() };
+            results.push(error(unmatched, call_site));
+            self.unread(results.into_iter());
+            return;
+        };
+
+        let expansions = Arc::make_mut(&mut self.expansions);
+        expansions.push(Expansion {
+            name: name(),
+            call_site,
+            def_site: def.span,
+            parent: token.expansion,
+        });
+        let id = ExpansionId(expansions.len() as u32);
+
+        let mut tokens = Vec::new();
+        match transcribe(&rule.body, &bindings, &mut Vec::new(), id, &mut tokens) {
+            Ok(()) => results.extend(tokens.into_iter().map(Ok)),
+            Err(()) => {
+                let mismatch = LexerError::MacroRepetitionMismatch { name: name() };
+                results.push(error(mismatch, call_site));
+            }
+        }
+        self.unread(results.into_iter());
+    }
+}
+
+type ParseResult<T> = Result<T, (&'static str, Span)>;
+
+/// Parses the rules of a macro definition.
+///
+/// Repetition operators are read from the source, as the lexer may have merged them with the
+/// characters that follow into another operator, like `**`, or reported them as unknown
+/// operators if the operator table doesn't define them.
+struct DefParser<'a, 'src> {
+    source: &'src str,
+    operators: &'a OperatorTable,
+    /// The errors found in the definition, from which the unknown operators taken as repetition
+    /// operators are removed.
+    errors: Vec<Spanned<LexerError>>,
+}
+
+type Trees<'a, 'src> = std::iter::Peekable<std::slice::Iter<'a, TokenTree<'src>>>;
+
+impl<'src> DefParser<'_, 'src> {
+    /// Parses the rules of a definition, `(pattern) => { body }` separated by `;`.
+    fn rules(&mut self, trees: &[TokenTree<'src>]) -> ParseResult<Vec<Rule<'src>>> {
+        let mut rules = Vec::new();
+        let mut trees = trees.iter().peekable();
+        while let Some(tree) = next_significant(&mut trees) {
+            let TokenTree::Group(pattern) = tree else {
+                return Err(("expected a pattern in delimiters", tree.span()));
+            };
+            match next_significant(&mut trees) {
+                Some(TokenTree::Token(token)) if token.kind == TokenKind::FatArrow => {}
+                _ => return Err(("expected `=>` after the pattern", pattern.span())),
+            }
+            let Some(TokenTree::Group(body)) = next_significant(&mut trees) else {
+                return Err(("expected a body in delimiters", pattern.span()));
+            };
+            rules.push(Rule {
+                pattern: self.trees(&pattern.trees, true)?,
+                body: self.trees(&body.trees, false)?,
+            });
+
+            match next_significant(&mut trees) {
+                Some(TokenTree::Token(token)) if token.kind == TokenKind::Semicolon => {}
+                None => break,
+                Some(tree) => return Err(("expected `;` between rules", tree.span())),
+            }
+        }
+        Ok(rules)
+    }
+
+    fn trees(
+        &mut self,
+        trees: &[TokenTree<'src>],
+        pattern: bool,
+    ) -> ParseResult<Vec<MacroTree<'src>>> {
+        let mut parsed = Vec::new();
+        let mut trees = trees.iter().peekable();
+        while let Some(tree) = next_significant(&mut trees) {
+            let token = match tree {
+                TokenTree::Group(group) => {
+                    parsed.push(MacroTree::Group {
+                        delimiter: group.delimiter,
+                        open: group.open,
+                        close: group.close,
+                        file: group.file,
+                        trees: self.trees(&group.trees, pattern)?,
+                    });
+                    continue;
+                }
+                TokenTree::Token(token) => token,
+            };
+
+            match token.kind {
+                TokenKind::DirectiveIdentifier if pattern => {
+                    let fragment =
+                        match (next_significant(&mut trees), next_significant(&mut trees)) {
+                            (Some(TokenTree::Token(colon)), Some(TokenTree::Token(fragment)))
+                                if colon.kind == TokenKind::Colon =>
+                            {
+                                fragment
+                            }
+                            _ => {
+                                return Err((
+                                    "expected `:` and a fragment after the metavariable",
+                                    token.span,
+                                ));
+                            }
+                        };
+                    let fragment = match fragment.slice {
+                        "ident" => Fragment::Ident,
+                        "literal" => Fragment::Literal,
+                        "tt" => Fragment::Tt,
+                        _ => {
+                            return Err((
+                                "unknown fragment, expected `ident`, `literal` or `tt`",
+                                fragment.span,
+                            ));
+                        }
+                    };
+                    parsed.push(MacroTree::Var {
+                        token: token.clone(),
+                        fragment: Some(fragment),
+                    });
+                }
+                TokenKind::DirectiveIdentifier => parsed.push(MacroTree::Var {
+                    token: token.clone(),
+                    fragment: None,
+                }),
+                TokenKind::Dollar => {
+                    let Some(TokenTree::Group(group)) = next_significant(&mut trees) else {
+                        return Err(("expected `(` after `$`", token.span));
+                    };
+                    let repeated = self.trees(&group.trees, pattern)?;
+                    let (separator, op) = match self.repeat_op(token, &mut trees) {
+                        Some(op) => (None, Some(op)),
+                        None => match next_significant(&mut trees) {
+                            Some(TokenTree::Token(separator)) => {
+                                (Some(separator.clone()), self.repeat_op(token, &mut trees))
+                            }
+                            _ => (None, None),
+                        },
+                    };
+                    let Some((op, rest)) = op else {
+                        return Err((
+                            "expected `*`, `+` or `?` after the repetition",
+                            group.span(),
+                        ));
+                    };
+                    parsed.push(MacroTree::Repeat {
+                        trees: repeated,
+                        separator,
+                        op,
+                    });
+                    parsed.extend(rest.into_iter().map(MacroTree::Token));
+                }
+                _ => parsed.push(MacroTree::Token(token.clone())),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Takes the repetition operator of the repetition started by `dollar` off the start of the
+    /// next operator in `trees`, or of the next unknown operator before it. Returns it with the
+    /// tokens lexed from the rest of that operator.
+    fn repeat_op(
+        &mut self,
+        dollar: &Token<'src>,
+        trees: &mut Trees<'_, 'src>,
+    ) -> Option<(RepeatOp, Vec<Token<'src>>)> {
+        skip_comments(trees);
+        let next = trees.peek().map(|tree| tree.span().start);
+        let unknown = self.errors.iter().position(|error| {
+            error.value == LexerError::UnknownOperator
+                && error.span.start >= dollar.span.end
+                && next.is_none_or(|next| error.span.start < next)
+        });
+
+        let (span, index) = match (unknown, trees.peek()) {
+            (Some(index), _) => (self.errors[index].span, Some(index)),
+            (None, Some(TokenTree::Token(token)))
+                if matches!(token.kind, TokenKind::Operator(_)) =>
+            {
+                (token.span, None)
+            }
+            _ => return None,
+        };
+        let op = RepeatOp::starting(&self.source[span.range()])?;
+        match index {
+            Some(index) => {
+                self.errors.remove(index);
+            }
+            None => {
+                trees.next();
+            }
+        }
+
+        // Lex the rest like the lexer would have without the repetition operator
+        let mut rest = Vec::new();
+        let mut start = span.start + 1;
+        while start < span.end {
+            let text = &self.source[start..span.end];
+            let Some((id, len)) = self.operators.trie().longest_match(text) else {
+                let error = Spanned::new(LexerError::UnknownOperator, Span::new(start, span.end));
+                self.errors
+                    .insert(index.unwrap_or(self.errors.len()), error);
+                break;
+            };
+            rest.push(Token {
+                expansion: dollar.expansion,
+                file: dollar.file,
+                ..Token::new(
+                    TokenKind::Operator(id),
+                    &text[..len],
+                    Span::new(start, start + len),
+                )
+            });
+            start += len;
+        }
+        Some((op, rest))
+    }
+}
+
+/// Skips the comments at the start of `trees`.
+fn skip_comments(trees: &mut Trees) {
+    while trees
+        .next_if(|tree| matches!(tree, TokenTree::Token(token) if token.kind.is_trivia()))
+        .is_some()
+    {}
+}
+
+fn next_significant<'a, 'src>(trees: &mut Trees<'a, 'src>) -> Option<&'a TokenTree<'src>> {
+    skip_comments(trees);
+    trees.next()
+}
+
+/// Matches `pattern` against a prefix of `input[*pos..]`, advancing `pos` past it.
+fn match_trees<'src>(
+    pattern: &[MacroTree<'src>],
+    input: &[TokenTree<'src>],
+    pos: &mut usize,
+    bindings: &mut Bindings<'src>,
+) -> bool {
+    for matcher in pattern {
+        match matcher {
+            MacroTree::Token(expected) => match input.get(*pos) {
+                Some(TokenTree::Token(token)) if same_token(token, expected) => *pos += 1,
+                _ => return false,
+            },
+            MacroTree::Group {
+                delimiter, trees, ..
+            } => match input.get(*pos) {
+                Some(TokenTree::Group(group)) if group.delimiter == *delimiter => {
+                    let mut inner = 0;
+                    if !match_trees(trees, &group.trees, &mut inner, bindings)
+                        || inner != group.trees.len()
+                    {
+                        return false;
+                    }
+                    *pos += 1;
+                }
+                _ => return false,
+            },
+            MacroTree::Var { token, fragment } => match input.get(*pos) {
+                Some(tree) if fragment.is_some_and(|fragment| fragment.matches(tree)) => {
+                    bindings.insert(&token.slice[1..], Binding::One(tree.clone()));
+                    *pos += 1;
+                }
+                _ => return false,
+            },
+            MacroTree::Repeat {
+                trees,
+                separator,
+                op,
+            } => {
+                let mut iterations = Vec::new();
+                while *op != RepeatOp::ZeroOrOne || iterations.is_empty() {
+                    let mut next = *pos;
+                    if let Some(separator) = separator
+                        && !iterations.is_empty()
+                    {
+                        match input.get(next) {
+                            Some(TokenTree::Token(token)) if same_token(token, separator) => {
+                                next += 1
+                            }
+                            _ => break,
+                        }
+                    }
+                    let start = next;
+                    let mut iteration = Bindings::new();
+                    if !match_trees(trees, input, &mut next, &mut iteration) || next == start {
+                        break;
+                    }
+                    *pos = next;
+                    iterations.push(iteration);
+                }
+                if *op == RepeatOp::OneOrMore && iterations.is_empty() {
+                    return false;
+                }
+
+                let mut names = Vec::new();
+                var_names(trees, &mut names);
+                for name in names {
+                    let bound = iterations
+                        .iter_mut()
+                        .map(|iteration| {
+                            iteration.remove(name).unwrap_or(Binding::Many(Vec::new()))
+                        })
+                        .collect();
+                    bindings.insert(name, Binding::Many(bound));
+                }
+            }
+        }
+    }
+    true
+}
+
+fn same_token(token: &Token, expected: &Token) -> bool {
+    token.kind == expected.kind && token.slice == expected.slice
+}
+
+fn var_names<'src>(trees: &[MacroTree<'src>], names: &mut Vec<&'src str>) {
+    for tree in trees {
+        match tree {
+            MacroTree::Token(_) => {}
+            MacroTree::Group { trees, .. } | MacroTree::Repeat { trees, .. } => {
+                var_names(trees, names)
+            }
+            MacroTree::Var { token, .. } => names.push(&token.slice[1..]),
+        }
+    }
+}
+
+/// Looks up the binding of `name` for the repetition iterations in `indices`.
+fn lookup<'a, 'src>(
+    bindings: &'a Bindings<'src>,
+    name: &str,
+    indices: &[usize],
+) -> Option<&'a Binding<'src>> {
+    let mut binding = bindings.get(name)?;
+    for &index in indices {
+        match binding {
+            Binding::Many(iterations) => binding = iterations.get(index)?,
+            Binding::One(_) => break,
+        }
+    }
+    Some(binding)
+}
+
+/// Writes the expansion of `body` to `out`, marking the tokens of `body` with `id`.
+///
+/// Fails if a repetition's metavariables repeat a different number of times, or a metavariable
+/// is used at the wrong repetition depth.
+fn transcribe<'src>(
+    body: &[MacroTree<'src>],
+    bindings: &Bindings<'src>,
+    indices: &mut Vec<usize>,
+    id: ExpansionId,
+    out: &mut Vec<Token<'src>>,
+) -> Result<(), ()> {
+    for tree in body {
+        match tree {
+            MacroTree::Token(token) => out.push(Token {
+                expansion: id,
+                ..token.clone()
+            }),
+            MacroTree::Group {
+                delimiter,
+                open,
+                close,
+                file,
+                trees,
+            } => {
+                out.push(Token {
+                    file: *file,
+                    ..delimiter.open_token(*open, id)
+                });
+                transcribe(trees, bindings, indices, id, out)?;
+                out.push(Token {
+                    file: *file,
+                    ..delimiter.close_token(*close, id)
+                });
+            }
+            MacroTree::Var { token, .. } => match lookup(bindings, &token.slice[1..], indices) {
+                Some(Binding::One(tree)) => flatten(tree, out),
+                Some(Binding::Many(_)) => return Err(()),
+                // Not a metavariable, such as a directive
+                None => out.push(Token {
+                    expansion: id,
+                    ..token.clone()
+                }),
+            },
+            MacroTree::Repeat {
+                trees, separator, ..
+            } => {
+                let mut names = Vec::new();
+                var_names(trees, &mut names);
+                let mut counts =
+                    names
+                        .iter()
+                        .filter_map(|name| match lookup(bindings, name, indices) {
+                            Some(Binding::Many(iterations)) => Some(iterations.len()),
+                            _ => None,
+                        });
+                let count = counts.next().ok_or(())?;
+                if counts.any(|other| other != count) {
+                    return Err(());
+                }
+
+                for i in 0..count {
+                    if let Some(separator) = separator
+                        && i > 0
+                    {
+                        out.push(Token {
+                            expansion: id,
+                            ..separator.clone()
+                        });
+                    }
+                    indices.push(i);
+                    transcribe(trees, bindings, indices, id, out)?;
+                    indices.pop();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn flatten<'src>(tree: &TokenTree<'src>, out: &mut Vec<Token<'src>>) {
+    match tree {
+        TokenTree::Token(token) => out.push(token.clone()),
+        TokenTree::Group(group) => {
+            let (open, close) = (
+                group.delimiter.open_token(group.open, group.expansion),
+                group.delimiter.close_token(group.close, group.expansion),
+            );
+            out.push(Token {
+                file: group.file,
+                ..open
+            });
+            for tree in &group.trees {
+                flatten(tree, out);
+            }
+            out.push(Token {
+                file: group.file,
+                ..close
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExpansionId;
+    use crate::lexer::{Lexer, LexerError};
+    use crate::operator::{Associativity, OperatorDef, OperatorTable};
+
+    fn expand(source: &str) -> Vec<Result<String, LexerError>> {
+        Lexer::with_operators(source, OperatorTable::prelude())
+            .map(|result| {
+                result
+                    .map(|token| token.slice.to_string())
+                    .map_err(|e| e.value)
+            })
+            .collect()
+    }
+
+    fn slices(source: &str) -> String {
+        expand(source)
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn rules() {
+        let source = r"
+            @macro_rules pick {
+                (first $a:tt $b:tt) => { $a };
+                (second $a:tt $b:tt) => { $b };
+                ($x:ident) => { [$x] };
+            }
+            @pick(first 1 (2)) @pick[second 1 (2)] @pick{ x }
+        ";
+        assert_eq!(slices(source), "1 ( 2 ) [ x ]");
+    }
+
+    #[test]
+    fn repetitions() {
+        let source = r"
+            @macro_rules list {
+                ($($x:literal),*) => { [$($x);*] };
+            }
+            @macro_rules pairs {
+                ($($k:ident = $v:tt)+) => { $(($k, $v)) , + };
+            }
+            @list() @list(1, 2, 3) @pairs(a = 1 b = (2 + 3))
+        ";
+        assert_eq!(
+            slices(source),
+            "[ ] [ 1 ; 2 ; 3 ] ( a , 1 ) , ( b , ( 2 + 3 ) )"
+        );
+    }
+
+    #[test]
+    fn repetitions_with_default_operators() {
+        let source = r"
+            @macro_rules list {
+                ($($x:literal),*) => { [$($x)+] };
+                ($first:ident $($rest:ident)+) => { $first($($rest) *) };
+                ($x:ident ; $($y:literal)?) => { $($y)? };
+                (; $($x:literal) /* comma */ , /* many */ *) => { $($x)/**/+ };
+            }
+            @list(4, 5) @list(f a b) @list(z; 3) @list(z;) @list(; 4, 5)
+        ";
+        let tokens: Vec<_> = Lexer::new(source)
+            .map(|result| result.map(|token| token.slice).map_err(|e| e.value))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens.join(" "), "[ 4 5 ] f ( a b ) 3 4 5");
+
+        // Repetition operators don't depend on the operator table
+        let source = "@macro_rules m { ($($x:tt),*) => { [$($x)+] }; } @m(a, b)";
+        let tokens: Vec<_> = Lexer::with_operators(source, OperatorTable::new())
+            .map(|result| result.unwrap().slice)
+            .collect();
+        assert_eq!(tokens.join(" "), "[ a b ]");
+
+        let mut operators = OperatorTable::new();
+        for spelling in ["*", "**"] {
+            operators
+                .define(spelling, OperatorDef::infix(11, Associativity::Left))
+                .unwrap();
+        }
+        let source = "@macro_rules m { ($($x:ident)**) => { $($x)**2 }; } @m(a b *)";
+        let tokens: Vec<_> = Lexer::with_operators(source, operators)
+            .map(|result| result.unwrap().slice)
+            .collect();
+        assert_eq!(tokens.join(" "), "a b * 2");
+    }
+
+    #[test]
+    fn nested() {
+        let source = r"
+            @macro_rules twice { ($($e:tt)*) => { $($e)* $($e)* }; }
+            @macro_rules four { ($e:tt) => { @twice(@twice($e)) }; }
+            @four(x)
+        ";
+        assert_eq!(slices(source), "x x x x");
+    }
+
+    #[test]
+    fn unknown_macros_pass_through() {
+        assert_eq!(slices("@unknown(a)"), "@unknown ( a )");
+    }
+
+    #[test]
+    fn errors() {
+        let source = r"
+            @macro_rules one { (1) => { one }; }
+            @one(2) @one after
+        ";
+        assert_eq!(
+            expand(source),
+            vec![
+                Err(LexerError::NoMatchingMacroRule {
+                    name: "one".to_string()
+                }),
+                Err(LexerError::MissingMacroArguments {
+                    name: "one".to_string()
+                }),
+                Ok("after".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            expand("@macro_rules bad { ($x) => {}; } x"),
+            vec![
+                Err(LexerError::InvalidMacroDefinition {
+                    reason: "expected `:` and a fragment after the metavariable"
+                }),
+                Ok("x".to_string()),
+            ]
+        );
+
+        let results = expand("@macro_rules again { () => { @again() }; } @again()");
+        assert_eq!(
+            results,
+            vec![Err(LexerError::MacroRecursionLimit {
+                name: "again".to_string()
+            })]
+        );
+
+        let results = expand(
+            "@macro_rules zip { ($($a:literal)*; $($b:literal)*) => { $($a $b)* }; } @zip(1 2; 3)",
+        );
+        assert_eq!(
+            results,
+            vec![Err(LexerError::MacroRepetitionMismatch {
+                name: "zip".to_string()
+            })]
+        );
+    }
+
+    #[test]
+    fn hygiene() {
+        let source = "@macro_rules with_x { ($e:tt) => { x = $e }; } x = 1 @with_x(x)";
+        let mut lexer = Lexer::with_operators(source, OperatorTable::prelude());
+        let tokens: Vec<_> = lexer.by_ref().map(Result::unwrap).collect();
+        let xs: Vec<_> = tokens.iter().filter(|token| token.slice == "x").collect();
+        assert_eq!(xs.len(), 3);
+        assert!(xs[0].expansion.is_root());
+        assert!(!xs[1].expansion.is_root());
+        assert!(xs[2].expansion.is_root(), "arguments keep their context");
+
+        let expansion = lexer.expansion(xs[1].expansion).unwrap();
+        assert_eq!(expansion.name, "with_x");
+        assert_eq!(&source[expansion.call_site.range()], "@with_x(x)");
+        assert!(source[expansion.def_site.range()].starts_with("@macro_rules with_x"));
+        assert_eq!(expansion.parent, ExpansionId::ROOT);
+        // Tokens of the body point into the definition
+        assert_eq!(&source[xs[1].span.range()], "x");
+        assert!(expansion.def_site.start <= xs[1].span.start);
+        assert!(xs[1].span.end <= expansion.def_site.end);
+    }
+
+    #[test]
+    fn backtrace() {
+        let source = r"
+            @macro_rules inner { () => { y }; }
+            @macro_rules outer { () => { @inner() }; }
+            @outer()
+        ";
+        let mut lexer = Lexer::new(source);
+        let y = lexer.next().unwrap().unwrap();
+        let names: Vec<_> = lexer
+            .backtrace(y.expansion)
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, ["inner", "outer"]);
+    }
+
+    #[test]
+    fn trivia_disables_expansion() {
+        let mut lexer = Lexer::with_trivia("@macro_rules m { () => {}; } @m()");
+        assert!(lexer.all(|result| result.is_ok()));
+    }
+}
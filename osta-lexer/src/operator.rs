@@ -0,0 +1,440 @@
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// The characters user-defined operators can be made of.
+pub const OPERATOR_CHARS: &[char] = &[
+    '!', '%', '&', '*', '+', '-', '.', '/', '<', '=', '>', '?', '^', '|', '~',
+];
+
+pub fn is_operator_char(c: char) -> bool {
+    OPERATOR_CHARS.contains(&c)
+}
+
+/// Checks that `spelling` can be the spelling of an operator.
+fn validate(spelling: &str) -> Result<(), OperatorError> {
+    if spelling.is_empty() {
+        return Err(OperatorError::Empty);
+    }
+    match spelling.chars().find(|&c| !is_operator_char(c)) {
+        Some(c) => Err(OperatorError::InvalidChar(c)),
+        None => Ok(()),
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum OperatorError {
+    #[error("operators cannot be empty")]
+    Empty,
+    #[error("invalid character `{0}` in operator")]
+    InvalidChar(char),
+    #[error("{fixity} operator `{spelling}` is already defined")]
+    Redefinition { spelling: String, fixity: Fixity },
+}
+
+#[derive(Debug, Default, Clone)]
+struct Node {
+    children: BTreeMap<char, usize>,
+    operator: Option<usize>,
+}
+
+/// A trie of operator spellings, used to split runs of operator characters greedily.
+///
+/// Operators are identified by the order in which they were inserted, which is the id carried by
+/// [`TokenKind::Operator`](crate::TokenKind::Operator).
+///
+/// Hardcoded tokens such as `->` and the comment openers `//` and `/*` always take precedence, so
+/// operators starting with them will never be matched.
+#[derive(Debug, Clone)]
+pub struct OperatorTrie {
+    nodes: Vec<Node>,
+    spellings: Vec<String>,
+}
+
+impl Default for OperatorTrie {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+            spellings: Vec::new(),
+        }
+    }
+}
+
+impl OperatorTrie {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `spelling` and returns its id. Inserting an existing spelling returns the id it
+    /// was first given.
+    pub fn insert(&mut self, spelling: &str) -> Result<usize, OperatorError> {
+        validate(spelling)?;
+
+        let mut node = 0;
+        for c in spelling.chars() {
+            node = match self.nodes[node].children.get(&c) {
+                Some(&child) => child,
+                None => {
+                    self.nodes.push(Node::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(c, child);
+                    child
+                }
+            };
+        }
+
+        Ok(*self.nodes[node].operator.get_or_insert_with(|| {
+            self.spellings.push(spelling.to_string());
+            self.spellings.len() - 1
+        }))
+    }
+
+    pub fn get(&self, spelling: &str) -> Option<usize> {
+        let mut node = 0;
+        for c in spelling.chars() {
+            node = *self.nodes[node].children.get(&c)?;
+        }
+        self.nodes[node].operator
+    }
+
+    pub fn spelling(&self, id: usize) -> Option<&str> {
+        self.spellings.get(id).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.spellings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spellings.is_empty()
+    }
+
+    /// Finds the longest operator `input` starts with, returning its id and length in bytes.
+    pub fn longest_match(&self, input: &str) -> Option<(usize, usize)> {
+        let mut node = 0;
+        let mut longest = None;
+        for (i, c) in input.char_indices() {
+            match self.nodes[node].children.get(&c) {
+                Some(&child) => node = child,
+                None => break,
+            }
+            if let Some(id) = self.nodes[node].operator {
+                longest = Some((id, i + c.len_utf8()));
+            }
+        }
+        longest
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Associativity {
+    Left,
+    Right,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fixity {
+    /// `op a`
+    Prefix,
+    /// `a op b`
+    Infix,
+    /// `a op`
+    Postfix,
+    /// `a op b part c ...`, an infix operator followed by more parts, each followed by an operand.
+    Mixfix,
+}
+
+impl Fixity {
+    /// Infix and mixfix operators are both parsed after a left operand and followed by a right
+    /// one, so a spelling can only be one of them.
+    fn overlaps(self, other: Fixity) -> bool {
+        let slot = |fixity| match fixity {
+            Fixity::Mixfix => Fixity::Infix,
+            fixity => fixity,
+        };
+        slot(self) == slot(other)
+    }
+}
+
+impl std::fmt::Display for Fixity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Fixity::Prefix => "prefix",
+            Fixity::Infix => "infix",
+            Fixity::Postfix => "postfix",
+            Fixity::Mixfix => "mixfix",
+        })
+    }
+}
+
+/// How an operator is parsed. Higher precedences bind tighter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorDef {
+    pub fixity: Fixity,
+    pub precedence: u32,
+    pub associativity: Associativity,
+    /// The spellings of the remaining parts of a mixfix operator, e.g. `["!!"]` for a ternary
+    /// `?` written `a ? b !! c`. Parts are operators too, so they can only use
+    /// [`OPERATOR_CHARS`]. Empty for every other fixity.
+    pub parts: Vec<String>,
+}
+
+impl OperatorDef {
+    pub fn prefix(precedence: u32) -> Self {
+        Self {
+            fixity: Fixity::Prefix,
+            precedence,
+            associativity: Associativity::Right,
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn infix(precedence: u32, associativity: Associativity) -> Self {
+        Self {
+            fixity: Fixity::Infix,
+            precedence,
+            associativity,
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn postfix(precedence: u32) -> Self {
+        Self {
+            fixity: Fixity::Postfix,
+            precedence,
+            associativity: Associativity::Left,
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn mixfix(
+        precedence: u32,
+        associativity: Associativity,
+        parts: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            fixity: Fixity::Mixfix,
+            precedence,
+            associativity,
+            parts: parts.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// The operators known to a lexer, mapping each [`TokenKind::Operator`](crate::TokenKind::Operator)
+/// id to its spelling and the ways it can be parsed.
+///
+/// A spelling can have one definition per fixity, so `-` can be both a prefix negation and an
+/// infix subtraction.
+#[derive(Debug, Default, Clone)]
+pub struct OperatorTable {
+    trie: OperatorTrie,
+    defs: Vec<Vec<OperatorDef>>,
+}
+
+impl OperatorTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The standard arithmetic, bitwise, comparison, logical and assignment operators.
+    pub fn prelude() -> Self {
+        use Associativity::{Left, Right};
+
+        let mut table = Self::new();
+        let mut define = |spellings: &[&str], def: OperatorDef| {
+            for spelling in spellings {
+                table.define(spelling, def.clone()).unwrap();
+            }
+        };
+        define(
+            &[
+                "=", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>=",
+            ],
+            OperatorDef::infix(1, Right),
+        );
+        define(&["||"], OperatorDef::infix(2, Left));
+        define(&["&&"], OperatorDef::infix(3, Left));
+        define(
+            &["==", "!=", "<", ">", "<=", ">="],
+            OperatorDef::infix(4, Associativity::None),
+        );
+        define(&["|"], OperatorDef::infix(5, Left));
+        define(&["^"], OperatorDef::infix(6, Left));
+        define(&["&"], OperatorDef::infix(7, Left));
+        define(&["<<", ">>"], OperatorDef::infix(8, Left));
+        define(&["+", "-"], OperatorDef::infix(9, Left));
+        define(&["*", "/", "%"], OperatorDef::infix(10, Left));
+        define(&["-", "!", "~"], OperatorDef::prefix(11));
+        define(&["?"], OperatorDef::postfix(12));
+        table
+    }
+
+    /// Defines `spelling` with `def`, returning its id.
+    ///
+    /// The table is left unchanged if the spelling or a part of a mixfix operator is invalid, or
+    /// the spelling already has a definition of the same fixity.
+    pub fn define(&mut self, spelling: &str, def: OperatorDef) -> Result<usize, OperatorError> {
+        validate(spelling)?;
+        for part in &def.parts {
+            validate(part)?;
+        }
+        if let Some(id) = self.trie.get(spelling)
+            && self.defs(id).iter().any(|d| d.fixity.overlaps(def.fixity))
+        {
+            return Err(OperatorError::Redefinition {
+                spelling: spelling.to_string(),
+                fixity: def.fixity,
+            });
+        }
+
+        let id = self.trie.insert(spelling)?;
+        for part in &def.parts {
+            self.trie.insert(part)?;
+        }
+        self.defs.resize_with(self.trie.len(), Vec::new);
+        self.defs[id].push(def);
+        Ok(id)
+    }
+
+    pub fn trie(&self) -> &OperatorTrie {
+        &self.trie
+    }
+
+    pub fn get(&self, spelling: &str) -> Option<usize> {
+        self.trie.get(spelling)
+    }
+
+    pub fn spelling(&self, id: usize) -> Option<&str> {
+        self.trie.spelling(id)
+    }
+
+    /// Returns every definition of the operator `id`. Mixfix parts have no definitions.
+    pub fn defs(&self, id: usize) -> &[OperatorDef] {
+        self.defs.get(id).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn prefix(&self, id: usize) -> Option<&OperatorDef> {
+        self.find(id, |fixity| fixity == Fixity::Prefix)
+    }
+
+    /// Returns the infix or mixfix definition of the operator `id`.
+    pub fn infix(&self, id: usize) -> Option<&OperatorDef> {
+        self.find(id, |fixity| fixity.overlaps(Fixity::Infix))
+    }
+
+    pub fn postfix(&self, id: usize) -> Option<&OperatorDef> {
+        self.find(id, |fixity| fixity == Fixity::Postfix)
+    }
+
+    fn find(&self, id: usize, predicate: impl Fn(Fixity) -> bool) -> Option<&OperatorDef> {
+        self.defs(id).iter().find(|def| predicate(def.fixity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Associativity, Fixity, OperatorDef, OperatorError, OperatorTable, OperatorTrie};
+
+    #[test]
+    fn insert() {
+        let mut trie = OperatorTrie::new();
+        assert_eq!(trie.insert("+"), Ok(0));
+        assert_eq!(trie.insert("++"), Ok(1));
+        assert_eq!(trie.insert("+"), Ok(0));
+        assert_eq!(trie.insert(""), Err(OperatorError::Empty));
+        assert_eq!(trie.insert("+a"), Err(OperatorError::InvalidChar('a')));
+        assert_eq!(trie.len(), 2);
+        assert_eq!(trie.get("++"), Some(1));
+        assert_eq!(trie.get("+++"), None);
+        assert_eq!(trie.spelling(1), Some("++"));
+    }
+
+    #[test]
+    fn longest_match() {
+        let mut trie = OperatorTrie::new();
+        let plus = trie.insert("+").unwrap();
+        let inc = trie.insert("++").unwrap();
+        let shl_assign = trie.insert("<<=").unwrap();
+        assert_eq!(trie.longest_match("+"), Some((plus, 1)));
+        assert_eq!(trie.longest_match("++"), Some((inc, 2)));
+        assert_eq!(trie.longest_match("+++"), Some((inc, 2)));
+        assert_eq!(trie.longest_match("+-"), Some((plus, 1)));
+        assert_eq!(trie.longest_match("<<= 1"), Some((shl_assign, 3)));
+        assert_eq!(trie.longest_match("<<"), None);
+        assert_eq!(trie.longest_match("-"), None);
+    }
+
+    #[test]
+    fn table() {
+        let mut table = OperatorTable::new();
+        let minus = table
+            .define("-", OperatorDef::infix(1, Associativity::Left))
+            .unwrap();
+        assert_eq!(table.define("-", OperatorDef::prefix(2)), Ok(minus));
+        assert_eq!(
+            table.define("-", OperatorDef::mixfix(3, Associativity::Left, ["?"])),
+            Err(OperatorError::Redefinition {
+                spelling: "-".to_string(),
+                fixity: Fixity::Mixfix
+            })
+        );
+        assert_eq!(table.infix(minus).map(|d| d.precedence), Some(1));
+        assert_eq!(table.prefix(minus).map(|d| d.precedence), Some(2));
+        assert_eq!(table.postfix(minus), None);
+
+        let ternary = table
+            .define("?", OperatorDef::mixfix(0, Associativity::Right, ["!!"]))
+            .unwrap();
+        let part = table.get("!!").unwrap();
+        assert_eq!(table.infix(ternary).map(|d| d.fixity), Some(Fixity::Mixfix));
+        assert_eq!(table.spelling(part), Some("!!"));
+        assert!(table.defs(part).is_empty());
+    }
+
+    #[test]
+    fn failed_definitions() {
+        let mut table = OperatorTable::new();
+        let plus = table.define("+", OperatorDef::prefix(1)).unwrap();
+        assert_eq!(
+            table.define(
+                "?",
+                OperatorDef::mixfix(0, Associativity::Right, ["!", "ok"])
+            ),
+            Err(OperatorError::InvalidChar('o'))
+        );
+        assert_eq!(
+            table.define("+", OperatorDef::prefix(2)),
+            Err(OperatorError::Redefinition {
+                spelling: "+".to_string(),
+                fixity: Fixity::Prefix
+            })
+        );
+        assert_eq!(table.get("?"), None);
+        assert_eq!(table.get("!"), None);
+        assert_eq!(table.trie().len(), 1);
+        assert_eq!(table.prefix(plus).map(|d| d.precedence), Some(1));
+    }
+
+    #[test]
+    fn prelude_collisions() {
+        let mut table = OperatorTable::prelude();
+        let plus = table.get("+").unwrap();
+        let add_assign = table.get("+=").unwrap();
+        let inc = table.define("++", OperatorDef::postfix(12)).unwrap();
+        assert_eq!(table.trie().longest_match("+ 1"), Some((plus, 1)));
+        assert_eq!(table.trie().longest_match("+= 1"), Some((add_assign, 2)));
+        assert_eq!(table.trie().longest_match("++= 1"), Some((inc, 2)));
+        assert_eq!(table.trie().longest_match("+++"), Some((inc, 2)));
+        assert_eq!(
+            table.infix(add_assign).map(|d| d.associativity),
+            Some(Associativity::Right)
+        );
+        assert!(
+            table.infix(table.get("*").unwrap()).unwrap().precedence
+                > table.infix(plus).unwrap().precedence
+        );
+        assert!(table.prefix(table.get("-").unwrap()).is_some());
+        assert!(table.prefix(plus).is_none());
+    }
+}
@@ -1,24 +1,67 @@
 use crate::LexerError;
+use crate::directive::FileId;
+use crate::keyword::reserved;
+use crate::lexer::LexerExtras;
+use crate::literal::{
+    LiteralError, parse_bin_float, parse_float, parse_hex_float, parse_int, split_suffix,
+    unescape_one,
+};
+use crate::macros::ExpansionId;
+use crate::operator::is_operator_char;
+use crate::span::{Span, Spanned};
 use logos::Logos;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 #[allow(dead_code)]
 pub struct Token<'src> {
     pub kind: TokenKind,
     pub slice: &'src str,
+    pub span: Span,
+    /// The macro expansion this token was written in, see [`ExpansionId`].
+    pub expansion: ExpansionId,
+    /// The file this token was read from, see [`FileId`].
+    pub file: FileId,
 }
 
 impl<'src> Token<'src> {
-    pub fn new(kind: TokenKind, slice: &'src str) -> Self {
-        Self { kind, slice }
+    pub fn new(kind: TokenKind, slice: &'src str, span: Span) -> Self {
+        Self {
+            kind,
+            slice,
+            span,
+            expansion: ExpansionId::ROOT,
+            file: FileId::ROOT,
+        }
+    }
+
+    /// Returns the name of an identifier, which is its slice without the `r#` of raw identifiers.
+    pub fn ident_name(&self) -> &'src str {
+        self.slice.strip_prefix("r#").unwrap_or(self.slice)
+    }
+
+    /// Returns the text of a doc comment without its `///`, `//!`, `/**`, `/*!` and `*/` markers.
+    pub fn doc_text(&self) -> Option<&'src str> {
+        if !self.kind.is_doc_comment() {
+            return None;
+        }
+        let text = &self.slice[3..];
+        Some(if self.slice.starts_with("/*") {
+            text.strip_suffix("*/").unwrap_or(text)
+        } else {
+            text
+        })
     }
 }
 
 #[derive(Logos, Clone, Debug, PartialEq)]
 #[logos(error = LexerError)]
-#[logos(skip r"[ \t\r\n\f]+")]
+#[logos(extras = LexerExtras)]
 #[logos(subpattern dec_int = r"[0-9]+(_+[0-9]+)*")]
+#[logos(subpattern bin_int = r"[01]+(_+[01]+)*")]
+#[logos(subpattern hex_int = r"[0-9a-fA-F]+(_+[0-9a-fA-F]+)*")]
 #[logos(subpattern pos_int = r"0*[1-9][0-9]*")]
+#[logos(subpattern int_suffix = r"[iu](?&pos_int)|isize|usize")]
+#[logos(subpattern float_suffix = r"f(?&pos_int)")]
 #[cfg_attr(feature = "unicode-identifiers", logos(subpattern ident = r"(\p{XID_Start}|_)\p{XID_Continue}*"
 ))]
 #[cfg_attr(
@@ -26,9 +69,28 @@ impl<'src> Token<'src> {
     logos(subpattern ident = r"[a-zA-Z_][a-zA-Z0-9_]*")
 )]
 pub enum TokenKind {
+    // ======
+    // Trivia
+    // ======
+    #[regex(r"[ \t\f]+|\r")]
+    Whitespace,
+    #[regex(r"\r?\n")]
+    Newline,
     #[token("/*", lex_block_comment)]
+    #[token("/**/")]
+    #[token("/***", lex_block_comment)]
     #[token("//", lex_line_comment)]
+    #[token("////", lex_line_comment)]
     Comment,
+    /// `/// ...` or `/** ... */`, documenting the item that follows.
+    #[token("/**", lex_block_comment)]
+    #[token("///", lex_line_comment)]
+    OuterDocComment,
+    /// `//! ...` or `/*! ... */`, documenting the enclosing item.
+    #[token("/*!", lex_block_comment)]
+    #[token("//!", lex_line_comment)]
+    InnerDocComment,
+    #[regex(r"[!%&*+\-./<=>?^|~]", lex_operator)]
     Operator(usize),
     // ========
     // Keywords
@@ -40,6 +102,45 @@ pub enum TokenKind {
     Static,
     #[token("pub")]
     Pub,
+    #[token("extern")]
+    Extern,
+    #[token("comptime")]
+    Comptime,
+    #[token("linear")]
+    Linear,
+    // Items
+    #[token("fn")]
+    Fn,
+    #[token("type")]
+    Type,
+    #[token("struct")]
+    Struct,
+    #[token("enum")]
+    Enum,
+    #[token("union")]
+    Union,
+    #[token("import")]
+    Import,
+    // Control flow
+    #[token("let")]
+    Let,
+    #[token("if")]
+    If,
+    #[token("else")]
+    Else,
+    #[token("while")]
+    While,
+    #[token("for")]
+    For,
+    #[token("return")]
+    Return,
+    // Values
+    #[token("true")]
+    True,
+    #[token("false")]
+    False,
+    #[token("null")]
+    Null,
     // Data types
     #[token("never")]
     Never,
@@ -59,7 +160,9 @@ pub enum TokenKind {
     // Atoms
     // =====
     // Identifiers
-    #[regex(r"(?&ident)")]
+    /// A name, or a raw identifier like `r#fn`, see [`Token::ident_name`].
+    #[regex(r"(?&ident)", lex_identifier)]
+    #[regex(r"r#(?&ident)")]
     Identifier,
     #[regex(r"@(?&ident)")]
     MacroIdentifier,
@@ -68,28 +171,52 @@ pub enum TokenKind {
     #[regex(r"\$(?&ident)")]
     DirectiveIdentifier,
     // Integers
-    #[regex("(?&dec_int)")]
-    DecInt,
-    #[regex(r"0[bB][01]+(_+[01]+)*")]
-    BinInt,
-    #[regex(r"0[oO][0-7]+(_+[0-7]+)*")]
-    OctInt,
-    #[regex(r"0[xX][0-9a-fA-F]+(_+[0-9a-fA-F]+)*")]
-    HexInt,
+    #[regex("(?&dec_int)((?&int_suffix)|(?&float_suffix))?", lex_number)]
+    DecInt(Option<NumberSuffix>),
+    #[regex(r"0[bB](?&bin_int)(?&int_suffix)?", lex_number)]
+    BinInt(Option<NumberSuffix>),
+    #[regex(r"0[oO][0-7]+(_+[0-7]+)*(?&int_suffix)?", lex_number)]
+    OctInt(Option<NumberSuffix>),
+    #[regex(r"0[xX](?&hex_int)(?&int_suffix)?", lex_number)]
+    HexInt(Option<NumberSuffix>),
     // Floats
-    #[regex(r"(?&dec_int)\.(?&dec_int)")]
-    Float,
+    #[regex(r"(?&dec_int)\.(?&dec_int)(?&float_suffix)?", lex_number)]
+    Float(Option<NumberSuffix>),
     #[regex(r"(?&dec_int)\.")]
     IntFloat,
-    #[regex(r"(?&dec_int)\.(?&dec_int)[eE][+-]?(?&dec_int)")]
-    FloatExp,
-    #[regex(r"(?&dec_int)[eE][+-]?(?&dec_int)")]
-    IntExp,
+    #[regex(
+        r"(?&dec_int)\.(?&dec_int)[eE][+-]?(?&dec_int)(?&float_suffix)?",
+        lex_number
+    )]
+    FloatExp(Option<NumberSuffix>),
+    #[regex(r"(?&dec_int)[eE][+-]?(?&dec_int)(?&float_suffix)?", lex_number)]
+    IntExp(Option<NumberSuffix>),
+    /// `0x1.8p3`, a hexadecimal mantissa scaled by a power of two.
+    #[regex(
+        r"0[xX](?&hex_int)(\.(?&hex_int)?)?[pP][+-]?(?&dec_int)(?&float_suffix)?",
+        lex_exact_float
+    )]
+    HexFloat(Option<NumberSuffix>),
+    /// `0b1.1p3`, a binary mantissa scaled by a power of two.
+    #[regex(
+        r"0[bB](?&bin_int)(\.(?&bin_int)?)?[pP][+-]?(?&dec_int)(?&float_suffix)?",
+        lex_exact_float
+    )]
+    BinFloat(Option<NumberSuffix>),
     // Strings
-    #[regex(r#""(?:[^"]|\\")*""#)]
+    #[token("\"", lex_string)]
     String,
+    #[token("b\"", lex_byte_string)]
+    ByteString,
+    /// `r"..."` or `r#"..."#`, closed by the first `"` followed by as many `#` as it opened
+    /// with. Backslashes are not escapes, so `r"a\"` is a complete raw string.
     #[regex(r#"r#*""#, lex_raw_string)]
     RawString,
+    // Characters
+    #[token("'", lex_char)]
+    Char,
+    #[token("b'", lex_byte)]
+    Byte,
     // =======
     // Symbols
     // =======
@@ -115,6 +242,117 @@ pub enum TokenKind {
     Semicolon,
     #[token("->")]
     Arrow,
+    #[token("=>")]
+    FatArrow,
+    #[token("$")]
+    Dollar,
+}
+
+/// The type suffix of a numeric literal, such as the `u8` in `255u8`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NumberSuffix {
+    Int(usize),
+    Uint(usize),
+    Isize,
+    Usize,
+    Float(usize),
+}
+
+impl NumberSuffix {
+    /// The width assumed for `isize` and `usize` when checking ranges, which is the widest
+    /// supported pointer size.
+    pub const MAX_POINTER_WIDTH: usize = 64;
+
+    /// Whether the integer `value` is representable by this type.
+    ///
+    /// Signed types accept a magnitude one past their maximum, as the literal may be negated.
+    pub fn fits_int(self, value: u128) -> bool {
+        let fits_bits = |bits: usize| bits >= 128 || value < 1 << bits;
+        match self {
+            NumberSuffix::Uint(width) => fits_bits(width),
+            NumberSuffix::Usize => fits_bits(Self::MAX_POINTER_WIDTH),
+            NumberSuffix::Int(width) => fits_bits(width - 1) || value == 1 << (width - 1),
+            NumberSuffix::Isize => {
+                let bits = Self::MAX_POINTER_WIDTH - 1;
+                fits_bits(bits) || value == 1 << bits
+            }
+            NumberSuffix::Float(_) => self.fits_float(value as f64),
+        }
+    }
+
+    /// Whether the float `value` is finite once rounded to this type. Only `f16`, `f32` and
+    /// `f64` are checked, the other widths being rejected by the lexer.
+    pub fn fits_float(self, value: f64) -> bool {
+        match self {
+            // Anything below the midpoint between f16::MAX and the next power of two rounds down
+            NumberSuffix::Float(16) => value < 65520.0,
+            NumberSuffix::Float(32) => (value as f32).is_finite(),
+            NumberSuffix::Float(64) => value.is_finite(),
+            NumberSuffix::Float(_) => true,
+            _ => value.fract() == 0.0 && value <= u128::MAX as f64 && self.fits_int(value as u128),
+        }
+    }
+}
+
+impl std::fmt::Display for NumberSuffix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumberSuffix::Int(width) => write!(f, "i{width}"),
+            NumberSuffix::Uint(width) => write!(f, "u{width}"),
+            NumberSuffix::Isize => f.write_str("isize"),
+            NumberSuffix::Usize => f.write_str("usize"),
+            NumberSuffix::Float(width) => write!(f, "f{width}"),
+        }
+    }
+}
+
+impl TokenKind {
+    /// Whether the token carries no meaning for the parser.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace | TokenKind::Newline | TokenKind::Comment
+        )
+    }
+
+    /// Whether the token is a literal, `true`, `false` and `null` included.
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::DecInt(_)
+                | TokenKind::BinInt(_)
+                | TokenKind::OctInt(_)
+                | TokenKind::HexInt(_)
+                | TokenKind::Float(_)
+                | TokenKind::IntFloat
+                | TokenKind::FloatExp(_)
+                | TokenKind::IntExp(_)
+                | TokenKind::HexFloat(_)
+                | TokenKind::BinFloat(_)
+                | TokenKind::String
+                | TokenKind::ByteString
+                | TokenKind::RawString
+                | TokenKind::Char
+                | TokenKind::Byte
+                | TokenKind::True
+                | TokenKind::False
+                | TokenKind::Null
+        )
+    }
+
+    pub fn is_doc_comment(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::OuterDocComment | TokenKind::InnerDocComment
+        )
+    }
+}
+
+fn lex_identifier(lexer: &mut logos::Lexer<TokenKind>) -> Result<(), LexerError> {
+    match reserved(lexer.slice(), lexer.extras.edition) {
+        Some(keyword) => Err(LexerError::ReservedKeyword { keyword }),
+        None => Ok(()),
+    }
 }
 
 fn lex_nty(lexer: &mut logos::Lexer<TokenKind>) -> Result<usize, LexerError> {
@@ -123,58 +361,209 @@ fn lex_nty(lexer: &mut logos::Lexer<TokenKind>) -> Result<usize, LexerError> {
     Ok(nty)
 }
 
-fn lex_raw_string(lexer: &mut logos::Lexer<TokenKind>) -> bool {
-    let hashes = lexer.slice().len() - 2;
-    let mut exiting = false;
-    let mut escape = false;
-    let mut count = hashes;
+fn lex_number(lexer: &mut logos::Lexer<TokenKind>) -> Result<Option<NumberSuffix>, LexerError> {
+    let (digits, suffix) = split_suffix(lexer.slice());
+    let Some(suffix) = parse_suffix(suffix)? else {
+        return Ok(None);
+    };
 
-    for c in lexer.remainder().chars() {
-        lexer.bump(1);
+    let fits = match suffix {
+        NumberSuffix::Float(_) => parse_float(digits).map(|value| suffix.fits_float(value)),
+        _ => parse_int(digits).map(|value| suffix.fits_int(value)),
+    };
+    match fits {
+        Ok(true) => Ok(Some(suffix)),
+        _ => Err(LexerError::LiteralOutOfRange {
+            suffix: Some(suffix),
+        }),
+    }
+}
 
-        if exiting {
-            if c == '#' {
-                if count == 1 {
-                    return true;
-                }
+/// Lexes a hexadecimal or binary float, checking that it fits the width of its suffix.
+fn lex_exact_float(
+    lexer: &mut logos::Lexer<TokenKind>,
+) -> Result<Option<NumberSuffix>, LexerError> {
+    let (digits, suffix) = split_suffix(lexer.slice());
+    let suffix = parse_suffix(suffix)?;
+    let width = match suffix {
+        Some(NumberSuffix::Float(width)) => width,
+        _ => 64,
+    };
 
-                count -= 1;
+    let parse = match &digits[..2] {
+        "0b" | "0B" => parse_bin_float,
+        _ => parse_hex_float,
+    };
+    match parse(digits, width) {
+        Err(LiteralError::FloatOverflow(_)) => Err(LexerError::LiteralOutOfRange { suffix }),
+        _ => Ok(suffix),
+    }
+}
 
-                continue;
-            } else {
-                count = hashes;
-                exiting = false;
+fn parse_suffix(suffix: &str) -> Result<Option<NumberSuffix>, LexerError> {
+    Ok(Some(match suffix {
+        "" => return Ok(None),
+        "isize" => NumberSuffix::Isize,
+        "usize" => NumberSuffix::Usize,
+        _ => {
+            let width = suffix[1..].parse::<usize>()?;
+            match &suffix[..1] {
+                "i" => NumberSuffix::Int(width),
+                "u" => NumberSuffix::Uint(width),
+                _ if matches!(width, 16 | 32 | 64) => NumberSuffix::Float(width),
+                _ => return Err(LexerError::UnsupportedFloatWidth { width }),
             }
         }
+    }))
+}
 
-        if c == '\\' {
-            escape = !escape;
-        } else if c == '"' && !escape {
-            exiting = true;
-        } else {
-            escape = false;
+fn lex_operator(lexer: &mut logos::Lexer<TokenKind>) -> Result<usize, LexerError> {
+    let input = &lexer.source()[lexer.span().start..];
+    if let Some((id, len)) = lexer.extras.operators.trie().longest_match(input) {
+        lexer.bump(len - 1);
+        return Ok(id);
+    }
+
+    // Consume the whole run, stopping before anything that lexes as a hardcoded token
+    let mut len = 1;
+    for (i, c) in input.char_indices().skip(1) {
+        let rest = &input[i..];
+        if !is_operator_char(c) || ["->", "=>", "//", "/*"].iter().any(|t| rest.starts_with(t)) {
+            break;
         }
+        len = i + c.len_utf8();
     }
+    lexer.bump(len - 1);
+    Err(LexerError::UnknownOperator)
+}
 
-    false
+fn lex_string(lexer: &mut logos::Lexer<TokenKind>) -> Result<(), LexerError> {
+    lex_quoted(lexer, '"', false).map(|_| ())
 }
 
-fn lex_line_comment(lexer: &mut logos::Lexer<TokenKind>) -> bool {
-    for c in lexer.remainder().chars() {
-        if c == '\r' || c == '\n' {
-            return true;
+fn lex_byte_string(lexer: &mut logos::Lexer<TokenKind>) -> Result<(), LexerError> {
+    lex_quoted(lexer, '"', true).map(|_| ())
+}
+
+fn lex_char(lexer: &mut logos::Lexer<TokenKind>) -> Result<(), LexerError> {
+    match lex_quoted(lexer, '\'', false).map_err(unterminated_char)? {
+        1 => Ok(()),
+        _ => Err(LexerError::InvalidCharLiteral),
+    }
+}
+
+fn lex_byte(lexer: &mut logos::Lexer<TokenKind>) -> Result<(), LexerError> {
+    match lex_quoted(lexer, '\'', true).map_err(unterminated_char)? {
+        1 => Ok(()),
+        _ => Err(LexerError::InvalidCharLiteral),
+    }
+}
+
+/// Reports an unterminated character or byte literal as such, rather than as a string.
+fn unterminated_char(error: LexerError) -> LexerError {
+    match error {
+        LexerError::UnterminatedString { start } => LexerError::UnterminatedChar { start },
+        error => error,
+    }
+}
+
+/// Lexes the rest of a quoted literal, returning the number of characters in it or the first
+/// invalid escape or character found. Any further ones are queued in the extras, to be reported
+/// after it, each covering the literal from its escape up to the next one so the slices of the
+/// errors still add up to the literal.
+///
+/// Character literals can't span lines, so a `'` literal is unterminated at the end of its line.
+fn lex_quoted(
+    lexer: &mut logos::Lexer<TokenKind>,
+    quote: char,
+    bytes: bool,
+) -> Result<usize, LexerError> {
+    let start = lexer.span().end;
+    let remainder = lexer.remainder();
+    let mut errors = Vec::new();
+    let mut count = 0;
+    let mut i = 0;
+
+    while let Some(c) = remainder[i..].chars().next() {
+        count += 1;
+        match c {
+            '\n' | '\r' if quote == '\'' => break,
+            c if c == quote => {
+                lexer.bump(i + 1);
+                let mut errors = errors.into_iter().peekable();
+                let Some((_, first)) = errors.next() else {
+                    return Ok(count - 1);
+                };
+                while let Some((at, error)) = errors.next() {
+                    let end = errors.peek().map_or(start + i + 1, |(next, _)| *next);
+                    let queued = Spanned::new(error, Span::new(at, end));
+                    lexer.extras.errors.push_back(queued);
+                }
+                return Err(first);
+            }
+            '\\' => match unescape_one(&remainder[i + 1..], bytes) {
+                Ok((_, len)) => i += 1 + len,
+                Err((kind, len)) => {
+                    let span = Span::new(start + i, start + i + 1 + len);
+                    errors.push((
+                        span.start,
+                        match kind {
+                            LiteralError::InvalidUnicodeEscape { .. } => {
+                                LexerError::InvalidUnicodeEscape { span }
+                            }
+                            _ => LexerError::InvalidEscape { span },
+                        },
+                    ));
+                    i += 1 + len;
+                }
+            },
+            c => {
+                if bytes && !c.is_ascii() {
+                    let span = Span::new(start + i, start + i + c.len_utf8());
+                    errors.push((span.start, LexerError::NonAsciiByte { span }));
+                }
+                i += c.len_utf8();
+            }
+        }
+    }
+
+    // Recover at the end of the line, where the closing quote was most likely forgotten
+    let line_end = remainder.find(['\r', '\n']).unwrap_or(remainder.len());
+    lexer.bump(line_end);
+    Err(LexerError::UnterminatedString {
+        start: lexer.span().start,
+    })
+}
+
+fn lex_raw_string(lexer: &mut logos::Lexer<TokenKind>) -> Result<(), LexerError> {
+    let hashes = lexer.slice().len() - 2;
+    let terminator = format!("\"{}", "#".repeat(hashes));
+    match lexer.remainder().find(&terminator) {
+        Some(end) => {
+            lexer.bump(end + terminator.len());
+            Ok(())
+        }
+        None => {
+            // Raw strings usually span several lines, so anything after the opening is suspect
+            lexer.bump(lexer.remainder().len());
+            Err(LexerError::UnterminatedRawString { hashes })
         }
-        lexer.bump(1);
     }
+}
+
+fn lex_line_comment(lexer: &mut logos::Lexer<TokenKind>) -> bool {
+    let end = lexer.remainder().find(['\r', '\n']);
+    lexer.bump(end.unwrap_or(lexer.remainder().len()));
     true
 }
 
 fn lex_block_comment(lexer: &mut logos::Lexer<TokenKind>) -> Result<(), LexerError> {
     let mut depth = 1;
-    let mut prev = '\0';
+    // The `*` in `/***` can already be part of the closing `*/`
+    let mut prev = if lexer.slice() == "/***" { '*' } else { '\0' };
 
     for c in lexer.remainder().chars() {
-        lexer.bump(1);
+        lexer.bump(c.len_utf8());
 
         if prev == '*' && c == '/' {
             depth -= 1;
@@ -0,0 +1,344 @@
+use crate::directive::FileId;
+use crate::lexer::{Lexer, LexerError, TokenResult};
+use crate::macros::ExpansionId;
+use crate::span::{Span, Spanned};
+use crate::token::{Token, TokenKind};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Delimiter {
+    /// `( ... )`
+    Paren,
+    /// `{ ... }`
+    Brace,
+    /// `[ ... ]`
+    Bracket,
+}
+
+impl Delimiter {
+    pub fn from_open(kind: &TokenKind) -> Option<Self> {
+        match kind {
+            TokenKind::LParen => Some(Delimiter::Paren),
+            TokenKind::LBrace => Some(Delimiter::Brace),
+            TokenKind::LBracket => Some(Delimiter::Bracket),
+            _ => None,
+        }
+    }
+
+    pub fn from_close(kind: &TokenKind) -> Option<Self> {
+        match kind {
+            TokenKind::RParen => Some(Delimiter::Paren),
+            TokenKind::RBrace => Some(Delimiter::Brace),
+            TokenKind::RBracket => Some(Delimiter::Bracket),
+            _ => None,
+        }
+    }
+
+    /// Returns a token for the opening delimiter, as if it was written at `span`.
+    pub fn open_token<'src>(&self, span: Span, expansion: ExpansionId) -> Token<'src> {
+        let (kind, slice) = match self {
+            Delimiter::Paren => (TokenKind::LParen, "("),
+            Delimiter::Brace => (TokenKind::LBrace, "{"),
+            Delimiter::Bracket => (TokenKind::LBracket, "["),
+        };
+        Token {
+            expansion,
+            ..Token::new(kind, slice, span)
+        }
+    }
+
+    /// Returns a token for the closing delimiter, as if it was written at `span`.
+    pub fn close_token<'src>(&self, span: Span, expansion: ExpansionId) -> Token<'src> {
+        let (kind, slice) = match self {
+            Delimiter::Paren => (TokenKind::RParen, ")"),
+            Delimiter::Brace => (TokenKind::RBrace, "}"),
+            Delimiter::Bracket => (TokenKind::RBracket, "]"),
+        };
+        Token {
+            expansion,
+            ..Token::new(kind, slice, span)
+        }
+    }
+
+    pub fn open(&self) -> char {
+        match self {
+            Delimiter::Paren => '(',
+            Delimiter::Brace => '{',
+            Delimiter::Bracket => '[',
+        }
+    }
+
+    pub fn close(&self) -> char {
+        match self {
+            Delimiter::Paren => ')',
+            Delimiter::Brace => '}',
+            Delimiter::Bracket => ']',
+        }
+    }
+}
+
+impl fmt::Display for Delimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.open(), self.close())
+    }
+}
+
+/// A token, or a delimited group of token trees.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenTree<'src> {
+    Token(Token<'src>),
+    Group(Group<'src>),
+}
+
+/// Token trees enclosed in matching delimiters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group<'src> {
+    pub delimiter: Delimiter,
+    pub open: Span,
+    /// The span of the closing delimiter, or an empty span where it was expected if the group is
+    /// unclosed.
+    pub close: Span,
+    /// The expansion the opening delimiter was written in.
+    pub expansion: ExpansionId,
+    /// The file the opening delimiter was read from.
+    pub file: FileId,
+    pub trees: Vec<TokenTree<'src>>,
+}
+
+impl Group<'_> {
+    /// Returns the span from the opening to the closing delimiter, inclusive.
+    pub fn span(&self) -> Span {
+        self.open.to(self.close)
+    }
+}
+
+impl<'src> TokenTree<'src> {
+    pub fn span(&self) -> Span {
+        match self {
+            TokenTree::Token(token) => token.span,
+            TokenTree::Group(group) => group.span(),
+        }
+    }
+
+    /// Groups the remaining tokens of `lexer` by their delimiters.
+    ///
+    /// Unbalanced delimiters are reported and recovered from, so the result covers every token:
+    /// a closing delimiter that doesn't match the innermost group closes the nearest enclosing
+    /// group it does match, and one that matches no group is dropped.
+    pub fn parse_all(lexer: &mut Lexer<'src>) -> (Vec<TokenTree<'src>>, Vec<Spanned<LexerError>>) {
+        let mut builder = TreeBuilder::default();
+        for result in lexer.by_ref() {
+            builder.push(result);
+        }
+        builder.finish(lexer.source().len())
+    }
+}
+
+/// Reads the group opened by `open` from `next`, stopping at its closing delimiter.
+///
+/// An unclosed group ends after the last token read.
+pub(crate) fn parse_group<'src>(
+    open: Token<'src>,
+    mut next: impl FnMut() -> Option<TokenResult<'src>>,
+) -> (Group<'src>, Vec<Spanned<LexerError>>) {
+    let mut end = open.span.end;
+    let mut builder = TreeBuilder::default();
+    builder.push(Ok(open));
+    while !builder.stack.is_empty() {
+        let Some(result) = next() else {
+            break;
+        };
+        end = match &result {
+            Ok(token) => token.span.end,
+            Err(error) => error.span.end,
+        };
+        builder.push(result);
+    }
+
+    let (mut trees, errors) = builder.finish(end);
+    match trees.pop() {
+        Some(TokenTree::Group(group)) => (group, errors),
+        _ => unreachable!("the opening delimiter always starts a group"),
+    }
+}
+
+#[derive(Default)]
+struct TreeBuilder<'src> {
+    stack: Vec<Group<'src>>,
+    trees: Vec<TokenTree<'src>>,
+    errors: Vec<Spanned<LexerError>>,
+}
+
+impl<'src> TreeBuilder<'src> {
+    fn push(&mut self, result: TokenResult<'src>) {
+        let token = match result {
+            Ok(token) => token,
+            Err(error) => {
+                self.errors.push(error);
+                return;
+            }
+        };
+
+        if let Some(delimiter) = Delimiter::from_open(&token.kind) {
+            self.stack.push(Group {
+                delimiter,
+                open: token.span,
+                close: token.span,
+                expansion: token.expansion,
+                file: token.file,
+                trees: Vec::new(),
+            });
+            return;
+        }
+
+        let Some(delimiter) = Delimiter::from_close(&token.kind) else {
+            self.push_tree(TokenTree::Token(token));
+            return;
+        };
+        let close = token.span;
+        let Some(depth) = self
+            .stack
+            .iter()
+            .rposition(|group| group.delimiter == delimiter)
+        else {
+            self.errors.push(Spanned::new(
+                LexerError::UnexpectedClosingDelimiter { delimiter },
+                close,
+            ));
+            return;
+        };
+
+        while self.stack.len() > depth + 1 {
+            let mut group = self.stack.pop().unwrap();
+            self.errors.push(Spanned::new(
+                LexerError::MismatchedDelimiter {
+                    expected: group.delimiter,
+                    found: delimiter,
+                    open: group.open,
+                    close,
+                },
+                close,
+            ));
+            group.close = Span::new(close.start, close.start);
+            self.push_tree(TokenTree::Group(group));
+        }
+        let mut group = self.stack.pop().unwrap();
+        group.close = close;
+        self.push_tree(TokenTree::Group(group));
+    }
+
+    fn push_tree(&mut self, tree: TokenTree<'src>) {
+        match self.stack.last_mut() {
+            Some(group) => group.trees.push(tree),
+            None => self.trees.push(tree),
+        }
+    }
+
+    /// Closes the groups still open at `end`.
+    fn finish(mut self, end: usize) -> (Vec<TokenTree<'src>>, Vec<Spanned<LexerError>>) {
+        while let Some(mut group) = self.stack.pop() {
+            self.errors.push(Spanned::new(
+                LexerError::UnclosedDelimiter {
+                    delimiter: group.delimiter,
+                    open: group.open,
+                },
+                group.open,
+            ));
+            group.close = Span::new(end, end);
+            self.push_tree(TokenTree::Group(group));
+        }
+
+        self.errors.sort_by_key(|error| error.span.start);
+        (self.trees, self.errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Delimiter, TokenTree};
+    use crate::lexer::{Lexer, LexerError};
+    use crate::span::Span;
+
+    /// Renders trees back to their delimiters, with each token as its slice.
+    fn shape(trees: &[TokenTree]) -> String {
+        trees
+            .iter()
+            .map(|tree| match tree {
+                TokenTree::Token(token) => token.slice.to_string(),
+                TokenTree::Group(group) => format!(
+                    "{}{}{}",
+                    group.delimiter.open(),
+                    shape(&group.trees),
+                    group.delimiter.close()
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn parse(source: &str) -> (String, Vec<LexerError>) {
+        let (trees, errors) = TokenTree::parse_all(&mut Lexer::new(source));
+        (shape(&trees), errors.into_iter().map(|e| e.value).collect())
+    }
+
+    #[test]
+    fn nesting() {
+        let (trees, errors) = TokenTree::parse_all(&mut Lexer::new("a (b [c] {d}) e"));
+        assert!(errors.is_empty());
+        assert_eq!(shape(&trees), "a (b [c] {d}) e");
+        assert_eq!(trees.len(), 3);
+        let TokenTree::Group(group) = &trees[1] else {
+            panic!("expected a group, found {:?}", trees[1]);
+        };
+        assert_eq!(group.delimiter, Delimiter::Paren);
+        assert_eq!(group.open, Span::new(2, 3));
+        assert_eq!(group.close, Span::new(12, 13));
+        assert_eq!(group.span(), Span::new(2, 13));
+        assert_eq!(trees[2].span(), Span::new(14, 15));
+    }
+
+    #[test]
+    fn mismatched() {
+        assert_eq!(
+            parse("(a [b) c"),
+            (
+                "(a [b]) c".to_string(),
+                vec![LexerError::MismatchedDelimiter {
+                    expected: Delimiter::Bracket,
+                    found: Delimiter::Paren,
+                    open: Span::new(3, 4),
+                    close: Span::new(5, 6),
+                }]
+            )
+        );
+    }
+
+    #[test]
+    fn unbalanced() {
+        assert_eq!(
+            parse("a ) {b"),
+            (
+                "a {b}".to_string(),
+                vec![
+                    LexerError::UnexpectedClosingDelimiter {
+                        delimiter: Delimiter::Paren
+                    },
+                    LexerError::UnclosedDelimiter {
+                        delimiter: Delimiter::Brace,
+                        open: Span::new(4, 5),
+                    },
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn lexer_errors() {
+        let (trees, errors) = parse("(a § b)");
+        assert_eq!(trees, "(a b)");
+        assert!(matches!(
+            errors[..],
+            [LexerError::UnexpectedChar { ch: '§', .. }]
+        ));
+    }
+}
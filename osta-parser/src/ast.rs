@@ -0,0 +1,228 @@
+//! The abstract syntax tree produced by the [`Parser`](crate::Parser).
+//!
+//! Nodes borrow names from the source, and each carries the span of source it was parsed from.
+
+use osta_lexer::{Span, Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile<'src> {
+    /// The text of the `//!` and `/*! */` comments documenting the file.
+    pub docs: Vec<&'src str>,
+    pub items: Vec<Item<'src>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ident<'src> {
+    pub name: &'src str,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Private,
+    /// `pub`, with the span of the keyword.
+    Public(Span),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Const,
+    Static,
+}
+
+impl Modifier {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Modifier::Const => "const",
+            Modifier::Static => "static",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item<'src> {
+    /// The text of the `///` and `/** */` comments documenting the item.
+    pub docs: Vec<&'src str>,
+    pub visibility: Visibility,
+    pub kind: ItemKind<'src>,
+    /// The span from the visibility to the end of the item, docs excluded.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemKind<'src> {
+    /// `const NAME: Type = value;`
+    Const(Global<'src>),
+    /// `static NAME: Type = value;`
+    Static(Global<'src>),
+    Fn(Fn<'src>),
+    /// `type Name = Type;`
+    TypeAlias(TypeAlias<'src>),
+}
+
+/// A constant or static.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global<'src> {
+    pub name: Ident<'src>,
+    pub ty: Option<Type<'src>>,
+    pub value: Option<Expr<'src>>,
+}
+
+/// `fn name(params) -> Type { body }`, or `;` instead of the body for external functions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fn<'src> {
+    /// The modifiers before `fn`, such as `const` for functions callable at compile time.
+    pub modifiers: Vec<(Modifier, Span)>,
+    pub name: Ident<'src>,
+    pub params: Vec<Param<'src>>,
+    pub ret: Option<Type<'src>>,
+    pub body: Option<Block<'src>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param<'src> {
+    pub name: Ident<'src>,
+    pub ty: Type<'src>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAlias<'src> {
+    pub name: Ident<'src>,
+    pub ty: Type<'src>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Type<'src> {
+    pub kind: TypeKind<'src>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind<'src> {
+    Primitive(PrimitiveType),
+    /// A type referred to by name.
+    Named(Ident<'src>),
+    /// `Name(args)`, a type constructor applied to types and values, such as `Vec(T, n)`.
+    Apply {
+        name: Ident<'src>,
+        args: Vec<TypeArg<'src>>,
+    },
+    /// `*T`
+    Pointer(Box<Type<'src>>),
+    /// `[T]`
+    Slice(Box<Type<'src>>),
+    /// `[T; len]`
+    Array {
+        elem: Box<Type<'src>>,
+        len: Box<Expr<'src>>,
+    },
+    /// `fn(params) -> ret`, where later parameters and the return type may refer to the values
+    /// of named parameters, as in `fn(n: usize) -> [u8; n]`.
+    Fn {
+        params: Vec<FnTypeParam<'src>>,
+        ret: Option<Box<Type<'src>>>,
+    },
+}
+
+/// An argument of a type constructor.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeArg<'src> {
+    Type(Type<'src>),
+    /// A value, such as the length in `Vec(T, n + 1)`.
+    ///
+    /// Arguments that also parse as types, like `n` or `f(x)`, are types, since what a name
+    /// refers to is only known once names are resolved.
+    Value(Expr<'src>),
+}
+
+impl TypeArg<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            TypeArg::Type(ty) => ty.span,
+            TypeArg::Value(expr) => expr.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FnTypeParam<'src> {
+    pub name: Option<Ident<'src>>,
+    pub ty: Type<'src>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimitiveType {
+    Never,
+    Void,
+    Int(usize),
+    Uint(usize),
+    Isize,
+    Usize,
+    Float(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr<'src> {
+    pub kind: ExprKind<'src>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind<'src> {
+    /// A number, string, character, `true`, `false` or `null`, whose value can be read from the
+    /// token.
+    Literal(Token<'src>),
+    Ident(Ident<'src>),
+    /// `(expr)`
+    Paren(Box<Expr<'src>>),
+    Block(Block<'src>),
+    /// `callee(args)`
+    Call {
+        callee: Box<Expr<'src>>,
+        args: Vec<Expr<'src>>,
+    },
+    /// `base[index]`
+    Index {
+        base: Box<Expr<'src>>,
+        index: Box<Expr<'src>>,
+    },
+    Prefix {
+        op: Operator<'src>,
+        operand: Box<Expr<'src>>,
+    },
+    Infix {
+        op: Operator<'src>,
+        lhs: Box<Expr<'src>>,
+        rhs: Box<Expr<'src>>,
+    },
+    Postfix {
+        op: Operator<'src>,
+        operand: Box<Expr<'src>>,
+    },
+    /// `a op b part c ...`, with one more operand than the operator has parts.
+    Mixfix {
+        op: Operator<'src>,
+        operands: Vec<Expr<'src>>,
+    },
+}
+
+/// An operator in an expression, or the first part of a mixfix operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operator<'src> {
+    /// The id of the operator in the [`OperatorTable`](osta_lexer::OperatorTable) it was parsed
+    /// with.
+    pub id: usize,
+    pub spelling: &'src str,
+    pub span: Span,
+}
+
+/// `{ statements; tail }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block<'src> {
+    /// The expressions ended by `;`, and blocks, which don't need one.
+    pub stmts: Vec<Expr<'src>>,
+    /// The expression ending the block without a `;`, which gives its value.
+    pub tail: Option<Box<Expr<'src>>>,
+    pub span: Span,
+}
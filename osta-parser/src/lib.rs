@@ -0,0 +1,6 @@
+//! Parses Osta sources into an abstract syntax tree.
+
+pub mod ast;
+mod parser;
+
+pub use parser::{ParseError, ParseResult, Parser, parse};
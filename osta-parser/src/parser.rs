@@ -0,0 +1,490 @@
+use crate::ast::{
+    Fn, Global, Ident, Item, ItemKind, Modifier, Param, SourceFile, TypeAlias, Visibility,
+};
+use osta_lexer::{Delimiter, Lexer, LexerError, Span, Spanned, Token, TokenKind};
+use thiserror::Error;
+
+mod expr;
+mod ty;
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum ParseError {
+    #[error(transparent)]
+    Lexer(#[from] LexerError),
+    #[error("expected {expected}, found {found}")]
+    Expected {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("duplicate `{}` modifier", .0.as_str())]
+    DuplicateModifier(Modifier),
+    #[error("`{}` is not allowed on {item}", modifier.as_str())]
+    InvalidModifier {
+        modifier: Modifier,
+        item: &'static str,
+    },
+    /// Non-associative operators of the same precedence, such as `a < b < c`, were chained.
+    #[error("`{spelling}` cannot be chained with operators of the same precedence")]
+    NonAssociative { spelling: String },
+    #[error("expected `{part}` to continue the `{operator}` operator")]
+    ExpectedOperatorPart { operator: String, part: String },
+}
+
+pub type ParseResult<T> = Result<T, Spanned<ParseError>>;
+
+/// Parses the items of a source file from the tokens of a [`Lexer`].
+///
+/// Errors are recovered from by skipping to the start of the next item, so one mistake doesn't
+/// hide the rest of the file. Comments are skipped, except for doc comments, which are attached
+/// to the item or file they document.
+pub struct Parser<'src> {
+    lexer: Lexer<'src>,
+    errors: Vec<Spanned<ParseError>>,
+    /// Outer doc comments read since the last item.
+    docs: Vec<&'src str>,
+    inner_docs: Vec<&'src str>,
+}
+
+/// Parses `source` with the prelude operators.
+pub fn parse(source: &str) -> (SourceFile<'_>, Vec<Spanned<ParseError>>) {
+    Parser::new(source).parse_source_file()
+}
+
+impl<'src> Parser<'src> {
+    /// Creates a parser for `source` with the operators of [`osta_lexer::OperatorTable::prelude`].
+    pub fn new(source: &'src str) -> Self {
+        Self::from_lexer(Lexer::new(source))
+    }
+
+    pub fn from_lexer(lexer: Lexer<'src>) -> Self {
+        Self {
+            lexer,
+            errors: Vec::new(),
+            docs: Vec::new(),
+            inner_docs: Vec::new(),
+        }
+    }
+
+    /// Parses the remaining tokens as items, returning them with the errors ordered by where
+    /// they start.
+    pub fn parse_source_file(mut self) -> (SourceFile<'src>, Vec<Spanned<ParseError>>) {
+        let mut items = Vec::new();
+        while self.peek().is_some() {
+            match self.item() {
+                Ok(item) => items.push(item),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.recover();
+                }
+            }
+            self.docs.clear();
+        }
+
+        self.errors.sort_by_key(|error| error.span.start);
+        let file = SourceFile {
+            docs: self.inner_docs,
+            items,
+        };
+        (file, self.errors)
+    }
+
+    fn item(&mut self) -> ParseResult<Item<'src>> {
+        let docs = std::mem::take(&mut self.docs);
+        let start = self.peek().map(|token| token.span).unwrap_or_default();
+        let visibility = match self.eat(TokenKind::Pub) {
+            Some(token) => Visibility::Public(token.span),
+            None => Visibility::Private,
+        };
+
+        let mut modifiers: Vec<(Modifier, Span)> = Vec::new();
+        loop {
+            let modifier = match self.peek_kind() {
+                Some(TokenKind::Const) => Modifier::Const,
+                Some(TokenKind::Static) => Modifier::Static,
+                _ => break,
+            };
+            let span = self.bump().span;
+            if modifiers.iter().any(|&(other, _)| other == modifier) {
+                let duplicate = ParseError::DuplicateModifier(modifier);
+                self.errors.push(Spanned::new(duplicate, span));
+            } else {
+                modifiers.push((modifier, span));
+            }
+        }
+
+        let kind = if self.at(TokenKind::Fn) {
+            ItemKind::Fn(self.function(modifiers)?)
+        } else if self.at(TokenKind::Type) {
+            self.reject_modifiers(&modifiers, "type aliases");
+            ItemKind::TypeAlias(self.type_alias()?)
+        } else if let Some((&(modifier, _), rest)) = modifiers.split_last() {
+            // The last modifier is the keyword that introduces the item
+            let (item, kind): (_, fn(_) -> _) = match modifier {
+                Modifier::Const => ("constants", ItemKind::Const),
+                Modifier::Static => ("statics", ItemKind::Static),
+            };
+            self.reject_modifiers(rest, item);
+            kind(self.global()?)
+        } else {
+            return Err(self.unexpected("an item"));
+        };
+
+        Ok(Item {
+            docs,
+            visibility,
+            kind,
+            span: start.to(self.lexer.prev_span()),
+        })
+    }
+
+    /// Parses a constant or static after its keyword.
+    fn global(&mut self) -> ParseResult<Global<'src>> {
+        let name = self.ident("a name")?;
+        let ty = match self.eat(TokenKind::Colon) {
+            Some(_) => Some(self.ty()?),
+            None => None,
+        };
+        let value = match self.eat_operator("=") {
+            Some(_) => Some(self.expr()?),
+            None => None,
+        };
+        self.expect(TokenKind::Semicolon, "`;`")?;
+        Ok(Global { name, ty, value })
+    }
+
+    fn function(&mut self, modifiers: Vec<(Modifier, Span)>) -> ParseResult<Fn<'src>> {
+        self.bump();
+        let name = self.ident("a function name")?;
+
+        self.expect(TokenKind::LParen, "`(`")?;
+        let mut params = Vec::new();
+        while self.eat(TokenKind::RParen).is_none() {
+            let name = self.ident("a parameter name")?;
+            self.expect(TokenKind::Colon, "`:`")?;
+            let ty = self.ty()?;
+            params.push(Param {
+                name,
+                span: name.span.to(ty.span),
+                ty,
+            });
+            if self.eat(TokenKind::Comma).is_none() {
+                self.expect(TokenKind::RParen, "`,` or `)`")?;
+                break;
+            }
+        }
+
+        let ret = match self.eat(TokenKind::Arrow) {
+            Some(_) => Some(self.ty()?),
+            None => None,
+        };
+        let body = if self.at(TokenKind::LBrace) {
+            Some(self.block()?)
+        } else {
+            let expected = if ret.is_some() {
+                "`{` or `;`"
+            } else {
+                "`->`, `{` or `;`"
+            };
+            self.expect(TokenKind::Semicolon, expected)?;
+            None
+        };
+
+        Ok(Fn {
+            modifiers,
+            name,
+            params,
+            ret,
+            body,
+        })
+    }
+
+    fn type_alias(&mut self) -> ParseResult<TypeAlias<'src>> {
+        self.bump();
+        let name = self.ident("a type name")?;
+        if self.eat_operator("=").is_none() {
+            return Err(self.unexpected("`=`"));
+        }
+        let ty = self.ty()?;
+        self.expect(TokenKind::Semicolon, "`;`")?;
+        Ok(TypeAlias { name, ty })
+    }
+
+    fn reject_modifiers(&mut self, modifiers: &[(Modifier, Span)], item: &'static str) {
+        for &(modifier, span) in modifiers {
+            let invalid = ParseError::InvalidModifier { modifier, item };
+            self.errors.push(Spanned::new(invalid, span));
+        }
+    }
+
+    /// Skips tokens up to the start of the next item, or past the `;` or block ending the
+    /// current one.
+    fn recover(&mut self) {
+        let mut depth = 0usize;
+        while let Some(token) = self.peek() {
+            if depth == 0 && starts_item(token) {
+                return;
+            }
+            let token = self.bump();
+            if Delimiter::from_open(&token.kind).is_some() {
+                depth += 1;
+            } else if Delimiter::from_close(&token.kind).is_some() {
+                depth = depth.saturating_sub(1);
+                if depth == 0 && token.kind == TokenKind::RBrace {
+                    return;
+                }
+            } else if depth == 0 && token.kind == TokenKind::Semicolon {
+                return;
+            }
+        }
+    }
+
+    /// Returns the next token, skipping comments and reporting lexer errors.
+    fn peek(&mut self) -> Option<&Token<'src>> {
+        loop {
+            match self.lexer.peek(0)? {
+                Ok(token) if token.kind.is_trivia() => {}
+                Ok(token) if token.kind.is_doc_comment() => {
+                    let text = token.doc_text().unwrap_or_default();
+                    match token.kind {
+                        TokenKind::InnerDocComment => self.inner_docs.push(text),
+                        _ => self.docs.push(text),
+                    }
+                }
+                Ok(_) => break,
+                Err(_) => {}
+            }
+            if let Some(Err(error)) = self.lexer.bump() {
+                self.errors
+                    .push(Spanned::new(error.value.into(), error.span));
+            }
+        }
+        match self.lexer.peek(0) {
+            Some(Ok(token)) => Some(token),
+            _ => None,
+        }
+    }
+
+    fn peek_kind(&mut self) -> Option<&TokenKind> {
+        self.peek().map(|token| &token.kind)
+    }
+
+    /// Consumes the token returned by [`Parser::peek`], which must exist.
+    fn bump(&mut self) -> Token<'src> {
+        self.peek();
+        match self.lexer.bump() {
+            Some(Ok(token)) => token,
+            _ => panic!("bumped past the end of the input"),
+        }
+    }
+
+    fn at(&mut self, kind: TokenKind) -> bool {
+        self.peek_kind() == Some(&kind)
+    }
+
+    fn eat(&mut self, kind: TokenKind) -> Option<Token<'src>> {
+        self.at(kind).then(|| self.bump())
+    }
+
+    fn eat_operator(&mut self, spelling: &str) -> Option<Token<'src>> {
+        let at = self.peek().is_some_and(|token| {
+            matches!(token.kind, TokenKind::Operator(_)) && token.slice == spelling
+        });
+        at.then(|| self.bump())
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &'static str) -> ParseResult<Token<'src>> {
+        match self.eat(kind) {
+            Some(token) => Ok(token),
+            None => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn ident(&mut self, expected: &'static str) -> ParseResult<Ident<'src>> {
+        let token = self.expect(TokenKind::Identifier, expected)?;
+        Ok(Ident {
+            name: token.ident_name(),
+            span: token.span,
+        })
+    }
+
+    /// Returns an error for the next token, or the end of the input, not being `expected`.
+    fn unexpected(&mut self, expected: &'static str) -> Spanned<ParseError> {
+        let found = match self.peek() {
+            Some(token) => format!("`{}`", token.slice),
+            None => "end of file".to_string(),
+        };
+        Spanned::new(ParseError::Expected { expected, found }, self.next_span())
+    }
+
+    /// Returns the span of the next token, or an empty span at the end of the input.
+    fn next_span(&mut self) -> Span {
+        let end = self.lexer.source().len();
+        self.peek().map_or(Span::new(end, end), |token| token.span)
+    }
+}
+
+fn starts_item(token: &Token) -> bool {
+    matches!(
+        token.kind,
+        TokenKind::Pub | TokenKind::Const | TokenKind::Static | TokenKind::Fn | TokenKind::Type
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseError, parse};
+    use crate::ast::{ExprKind, ItemKind, Modifier, PrimitiveType, Type, TypeKind, Visibility};
+    use osta_lexer::{LexerError, Span};
+
+    fn errors(source: &str) -> Vec<ParseError> {
+        parse(source).1.into_iter().map(|e| e.value).collect()
+    }
+
+    #[test]
+    fn globals() {
+        let (file, errors) = parse("pub const MAX: u32 = (1 << 8) - 1;\nstatic mut_count: usize;");
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(file.items.len(), 2);
+
+        let item = &file.items[0];
+        assert!(matches!(item.visibility, Visibility::Public(span) if span == Span::new(0, 3)));
+        assert_eq!(item.span, Span::new(0, 34));
+        let ItemKind::Const(global) = &item.kind else {
+            panic!("expected a constant, found {:?}", item.kind);
+        };
+        assert_eq!(global.name.name, "MAX");
+        assert_eq!(
+            global.ty,
+            Some(Type {
+                kind: TypeKind::Primitive(PrimitiveType::Uint(32)),
+                span: Span::new(15, 18)
+            })
+        );
+        let value = global.value.as_ref().unwrap();
+        assert!(matches!(&value.kind, ExprKind::Infix { op, .. } if op.spelling == "-"));
+        assert_eq!(value.span, Span::new(21, 33));
+
+        let ItemKind::Static(global) = &file.items[1].kind else {
+            panic!("expected a static, found {:?}", file.items[1].kind);
+        };
+        assert_eq!(file.items[1].visibility, Visibility::Private);
+        assert_eq!(global.value, None);
+    }
+
+    #[test]
+    fn functions() {
+        let source = r"
+            /// Adds two numbers.
+            pub const fn add(a: i32, b: i32,) -> i32 { { a } + b }
+            fn exit(r#type: i32) -> never;
+            fn noop() {}
+        ";
+        let (file, errors) = parse(source);
+        assert!(errors.is_empty(), "{errors:?}");
+
+        let item = &file.items[0];
+        assert_eq!(item.docs, [" Adds two numbers."]);
+        let ItemKind::Fn(function) = &item.kind else {
+            panic!("expected a function, found {:?}", item.kind);
+        };
+        assert_eq!(function.name.name, "add");
+        assert!(matches!(function.modifiers[..], [(Modifier::Const, _)]));
+        let params: Vec<_> = function.params.iter().map(|p| p.name.name).collect();
+        assert_eq!(params, ["a", "b"]);
+        assert_eq!(&source[function.params[1].span.range()], "b: i32");
+        assert!(matches!(
+            function.ret,
+            Some(Type {
+                kind: TypeKind::Primitive(PrimitiveType::Int(32)),
+                ..
+            })
+        ));
+        let body = function.body.as_ref().unwrap();
+        assert!(body.stmts.is_empty());
+        let Some(ExprKind::Infix { lhs, .. }) = body.tail.as_ref().map(|tail| &tail.kind) else {
+            panic!("expected an infix expression, found {:?}", body.tail);
+        };
+        assert!(matches!(lhs.kind, ExprKind::Block(_)));
+        assert_eq!(&source[body.span.range()], "{ { a } + b }");
+
+        let ItemKind::Fn(exit) = &file.items[1].kind else {
+            panic!("expected a function");
+        };
+        assert!(matches!(
+            exit.ret,
+            Some(Type {
+                kind: TypeKind::Primitive(PrimitiveType::Never),
+                ..
+            })
+        ));
+        assert_eq!(exit.params[0].name.name, "type");
+        assert_eq!(exit.body, None);
+        assert!(file.items[2].docs.is_empty());
+    }
+
+    #[test]
+    fn type_aliases() {
+        let (file, errors) = parse("//! Types.\npub type Size = usize; type Alias = Size;");
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(file.docs, [" Types."]);
+        let ItemKind::TypeAlias(alias) = &file.items[1].kind else {
+            panic!("expected a type alias");
+        };
+        assert_eq!(alias.name.name, "Alias");
+        assert!(matches!(alias.ty.kind, TypeKind::Named(ident) if ident.name == "Size"));
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_eq!(
+            errors("const const fn f(); static const X = 1; const type T = void;"),
+            [
+                ParseError::DuplicateModifier(Modifier::Const),
+                ParseError::InvalidModifier {
+                    modifier: Modifier::Static,
+                    item: "constants"
+                },
+                ParseError::InvalidModifier {
+                    modifier: Modifier::Const,
+                    item: "type aliases"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn recovery() {
+        let source = "fn f(x) { body } x y; const A = ; pub fn g() -> void § {}";
+        let (file, errors) = parse(source);
+        let names: Vec<_> = file
+            .items
+            .iter()
+            .map(|item| match &item.kind {
+                ItemKind::Fn(function) => function.name.name,
+                _ => "?",
+            })
+            .collect();
+        assert_eq!(names, ["g"]);
+        let expected = |expected, found: &str| ParseError::Expected {
+            expected,
+            found: found.to_string(),
+        };
+        assert_eq!(
+            errors.into_iter().map(|e| e.value).collect::<Vec<_>>(),
+            [
+                expected("`:`", "`)`"),
+                expected("an item", "`x`"),
+                expected("an expression", "`;`"),
+                ParseError::Lexer(LexerError::UnexpectedChar {
+                    ch: '§',
+                    span: Span::new(53, 55)
+                }),
+            ]
+        );
+
+        assert_eq!(
+            crate::parse("fn f() -> i32").1[0].value,
+            expected("`{` or `;`", "end of file")
+        );
+    }
+}
@@ -0,0 +1,440 @@
+//! Expressions, parsed by precedence climbing over the operators of the lexer.
+//!
+//! Operators are not part of the grammar: whether an operator token is prefix, infix, postfix
+//! or mixfix, and how tightly it binds, is looked up in the [`OperatorTable`] of the lexer when
+//! the token is reached, so operators defined while lexing parse like the built-in ones.
+//!
+//! Each precedence `p` is turned into binding powers `2p + 1` and `2p + 2`, the lower one on the
+//! side of the operand an operator lets operators of the same precedence take. A prefix operator
+//! takes its operand like a left-associative infix operator takes its right one, so with `-` and
+//! `**` at the same precedence, `-a ** b` is `(-a) ** b` if `**` is left-associative and
+//! `-(a ** b)` if it is right-associative, and postfix operators of that precedence apply to
+//! `-a`. Calls and indexing bind tighter than any operator. An operator with both an infix and a postfix
+//! definition is parsed as infix.
+//!
+//! [`OperatorTable`]: osta_lexer::OperatorTable
+
+use super::{ParseError, ParseResult, Parser};
+use crate::ast::{Block, Expr, ExprKind, Operator};
+use osta_lexer::{Associativity, OperatorDef, Spanned, TokenKind};
+
+/// Returns the binding powers on the left and right of an infix or mixfix operator.
+fn infix_power(def: &OperatorDef) -> (u32, u32) {
+    let power = def.precedence * 2 + 1;
+    match def.associativity {
+        Associativity::Right => (power + 1, power),
+        Associativity::Left | Associativity::None => (power, power + 1),
+    }
+}
+
+impl<'src> Parser<'src> {
+    /// Parses the remaining tokens as a single expression, returning it with the errors ordered
+    /// by where they start.
+    pub fn parse_expr(mut self) -> (Option<Expr<'src>>, Vec<Spanned<ParseError>>) {
+        let expr = match self.expr() {
+            Ok(expr) => {
+                if self.peek().is_some() {
+                    let error = self.unexpected("an operator or the end of the file");
+                    self.errors.push(error);
+                }
+                Some(expr)
+            }
+            Err(error) => {
+                self.errors.push(error);
+                None
+            }
+        };
+        self.errors.sort_by_key(|error| error.span.start);
+        (expr, self.errors)
+    }
+
+    pub(super) fn expr(&mut self) -> ParseResult<Expr<'src>> {
+        self.expr_with_power(0)
+    }
+
+    /// Parses an expression whose operators bind at least as tightly as `min_power`.
+    fn expr_with_power(&mut self, min_power: u32) -> ParseResult<Expr<'src>> {
+        let mut lhs = self.operand()?;
+        // The precedence of the non-associative operator `lhs` ends with, if any
+        let mut non_associative = None;
+
+        while let Some(token) = self.peek() {
+            let id = match token.kind {
+                TokenKind::LParen => {
+                    lhs = self.call(lhs)?;
+                    non_associative = None;
+                    continue;
+                }
+                TokenKind::LBracket => {
+                    self.bump();
+                    let index = self.expr()?;
+                    self.expect(TokenKind::RBracket, "`]`")?;
+                    lhs = Expr {
+                        span: lhs.span.to(self.lexer.prev_span()),
+                        kind: ExprKind::Index {
+                            base: Box::new(lhs),
+                            index: Box::new(index),
+                        },
+                    };
+                    non_associative = None;
+                    continue;
+                }
+                TokenKind::Operator(id) => id,
+                _ => break,
+            };
+
+            let operators = self.lexer.operators();
+            if let Some(def) = operators.infix(id).cloned() {
+                let (left, right) = infix_power(&def);
+                if left < min_power {
+                    break;
+                }
+                let op = self.operator();
+                if def.associativity == Associativity::None {
+                    if non_associative == Some(def.precedence) {
+                        let spelling = op.spelling.to_string();
+                        let error = ParseError::NonAssociative { spelling };
+                        return Err(Spanned::new(error, op.span));
+                    }
+                    non_associative = Some(def.precedence);
+                } else {
+                    non_associative = None;
+                }
+
+                let mut operands = vec![lhs];
+                for part in &def.parts {
+                    operands.push(self.expr()?);
+                    if self.eat_operator(part).is_none() {
+                        let error = ParseError::ExpectedOperatorPart {
+                            operator: op.spelling.to_string(),
+                            part: part.clone(),
+                        };
+                        return Err(Spanned::new(error, self.next_span()));
+                    }
+                }
+                operands.push(self.expr_with_power(right)?);
+
+                let span = operands[0].span.to(self.lexer.prev_span());
+                let kind = if def.parts.is_empty() {
+                    let rhs = operands.pop().unwrap();
+                    let lhs = operands.pop().unwrap();
+                    ExprKind::Infix {
+                        op,
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    }
+                } else {
+                    ExprKind::Mixfix { op, operands }
+                };
+                lhs = Expr { kind, span };
+            } else if let Some(precedence) = operators.postfix(id).map(|d| d.precedence) {
+                if precedence * 2 + 1 < min_power {
+                    break;
+                }
+                let op = self.operator();
+                lhs = Expr {
+                    span: lhs.span.to(op.span),
+                    kind: ExprKind::Postfix {
+                        op,
+                        operand: Box::new(lhs),
+                    },
+                };
+                non_associative = None;
+            } else {
+                break;
+            }
+        }
+        Ok(lhs)
+    }
+
+    /// Parses a literal, name, parenthesized expression, block or prefix operation.
+    fn operand(&mut self) -> ParseResult<Expr<'src>> {
+        let kind = match self.peek_kind() {
+            Some(kind) => kind.clone(),
+            None => return Err(self.unexpected("an expression")),
+        };
+        match kind {
+            kind if kind.is_literal() => {
+                let token = self.bump();
+                Ok(Expr {
+                    span: token.span,
+                    kind: ExprKind::Literal(token),
+                })
+            }
+            TokenKind::Identifier => {
+                let ident = self.ident("an expression")?;
+                Ok(Expr {
+                    span: ident.span,
+                    kind: ExprKind::Ident(ident),
+                })
+            }
+            TokenKind::LParen => {
+                let open = self.bump().span;
+                let expr = self.expr()?;
+                let close = self.expect(TokenKind::RParen, "`)`")?.span;
+                Ok(Expr {
+                    span: open.to(close),
+                    kind: ExprKind::Paren(Box::new(expr)),
+                })
+            }
+            TokenKind::LBrace => {
+                let block = self.block()?;
+                Ok(Expr {
+                    span: block.span,
+                    kind: ExprKind::Block(block),
+                })
+            }
+            TokenKind::Operator(id) => {
+                let Some(precedence) = self.lexer.operators().prefix(id).map(|d| d.precedence)
+                else {
+                    return Err(self.unexpected("an expression"));
+                };
+                let op = self.operator();
+                let operand = self.expr_with_power(precedence * 2 + 2)?;
+                Ok(Expr {
+                    span: op.span.to(operand.span),
+                    kind: ExprKind::Prefix {
+                        op,
+                        operand: Box::new(operand),
+                    },
+                })
+            }
+            _ => Err(self.unexpected("an expression")),
+        }
+    }
+
+    fn call(&mut self, callee: Expr<'src>) -> ParseResult<Expr<'src>> {
+        self.bump();
+        let mut args = Vec::new();
+        while self.eat(TokenKind::RParen).is_none() {
+            args.push(self.expr()?);
+            if self.eat(TokenKind::Comma).is_none() {
+                self.expect(TokenKind::RParen, "`,` or `)`")?;
+                break;
+            }
+        }
+        Ok(Expr {
+            span: callee.span.to(self.lexer.prev_span()),
+            kind: ExprKind::Call {
+                callee: Box::new(callee),
+                args,
+            },
+        })
+    }
+
+    pub(super) fn block(&mut self) -> ParseResult<Block<'src>> {
+        let open = self.expect(TokenKind::LBrace, "`{`")?.span;
+        let mut stmts = Vec::new();
+        loop {
+            if let Some(close) = self.eat(TokenKind::RBrace) {
+                return Ok(Block {
+                    stmts,
+                    tail: None,
+                    span: open.to(close.span),
+                });
+            }
+            let expr = self.expr()?;
+            let is_block = matches!(expr.kind, ExprKind::Block(_));
+            if self.eat(TokenKind::Semicolon).is_some() || is_block && !self.at(TokenKind::RBrace) {
+                stmts.push(expr);
+                continue;
+            }
+            let close = self.expect(TokenKind::RBrace, "`;` or `}`")?;
+            return Ok(Block {
+                stmts,
+                tail: Some(Box::new(expr)),
+                span: open.to(close.span),
+            });
+        }
+    }
+
+    /// Consumes the operator token returned by [`Parser::peek`].
+    fn operator(&mut self) -> Operator<'src> {
+        let token = self.bump();
+        let TokenKind::Operator(id) = token.kind else {
+            panic!("expected an operator token, found {:?}", token.kind);
+        };
+        Operator {
+            id,
+            spelling: token.slice,
+            span: token.span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Parser;
+    use crate::ast::{Expr, ExprKind};
+    use crate::parser::ParseError;
+    use osta_lexer::{Associativity, Lexer, OperatorDef, OperatorTable, Span};
+
+    /// Renders `expr` as an S-expression, with literals and names as written.
+    fn sexp(expr: &Expr) -> String {
+        let list = |head: &str, exprs: &mut dyn Iterator<Item = &Expr>| {
+            let mut out = format!("({head}");
+            for expr in exprs {
+                out += " ";
+                out += &sexp(expr);
+            }
+            out + ")"
+        };
+        match &expr.kind {
+            ExprKind::Literal(token) => token.slice.to_string(),
+            ExprKind::Ident(ident) => ident.name.to_string(),
+            ExprKind::Paren(expr) => sexp(expr),
+            ExprKind::Block(block) => {
+                list("{}", &mut block.stmts.iter().chain(block.tail.as_deref()))
+            }
+            ExprKind::Call { callee, args } => {
+                list("call", &mut std::iter::once(&**callee).chain(args))
+            }
+            ExprKind::Index { base, index } => list("[]", &mut [&**base, index].into_iter()),
+            ExprKind::Prefix { op, operand } | ExprKind::Postfix { op, operand } => {
+                let head = match expr.kind {
+                    ExprKind::Prefix { .. } => op.spelling.to_string(),
+                    _ => format!("post{}", op.spelling),
+                };
+                list(&head, &mut std::iter::once(&**operand))
+            }
+            ExprKind::Infix { op, lhs, rhs } => list(op.spelling, &mut [&**lhs, rhs].into_iter()),
+            ExprKind::Mixfix { op, operands } => list(op.spelling, &mut operands.iter()),
+        }
+    }
+
+    fn parse(source: &str) -> String {
+        let (expr, errors) = Parser::new(source).parse_expr();
+        assert!(errors.is_empty(), "{errors:?}");
+        sexp(&expr.unwrap())
+    }
+
+    fn errors(source: &str) -> Vec<ParseError> {
+        let (_, errors) = Parser::new(source).parse_expr();
+        errors.into_iter().map(|e| e.value).collect()
+    }
+
+    #[test]
+    fn atoms() {
+        assert_eq!(
+            parse("f(1, 0x2i8, 3.5e1, 4., 'c', b\"s\", true, null,)"),
+            "(call f 1 0x2i8 3.5e1 4. 'c' b\"s\" true null)"
+        );
+        assert_eq!(parse("a[i][j](x)[k]"), "([] (call ([] ([] a i) j) x) k)");
+        assert_eq!(parse("{ a; { b } c }"), "({} a ({} b) c)");
+        assert_eq!(parse("{ a; }"), "({} a)");
+        assert_eq!(parse("{}"), "({})");
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(parse("1 + 2 * 3 - 4"), "(- (+ 1 (* 2 3)) 4)");
+        assert_eq!(parse("(1 + 2) * 3"), "(* (+ 1 2) 3)");
+        assert_eq!(
+            parse("a = b += c || d && e"),
+            "(= a (+= b (|| c (&& d e))))"
+        );
+        assert_eq!(parse("-a * -b?"), "(* (- a) (- (post? b)))");
+        assert_eq!(parse("!f(x)[0] == y"), "(== (! ([] (call f x) 0)) y)");
+        assert_eq!(parse("a < b | c"), "(< a (| b c))");
+    }
+
+    #[test]
+    fn user_defined_operators() {
+        let mut table = OperatorTable::prelude();
+        table
+            .define("**", OperatorDef::infix(11, Associativity::Right))
+            .unwrap();
+        table.define("++", OperatorDef::postfix(13)).unwrap();
+        table
+            .define("?", OperatorDef::mixfix(0, Associativity::Right, ["!!"]))
+            .unwrap();
+        let parse = |source| {
+            let lexer = Lexer::with_operators(source, table.clone());
+            let (expr, errors) = Parser::from_lexer(lexer).parse_expr();
+            assert!(errors.is_empty(), "{errors:?}");
+            sexp(&expr.unwrap())
+        };
+        assert_eq!(parse("2 ** 3 ** 4 * 5"), "(* (** 2 (** 3 4)) 5)");
+        assert_eq!(parse("-i++"), "(- (post++ i))");
+        assert_eq!(parse("a ? b + 1 !! c ? d !! e"), "(? a (+ b 1) (? c d e))");
+
+        // Operators defined while lexing are parsed too
+        let mut lexer = Lexer::with_operators("a <> b", OperatorTable::prelude());
+        lexer
+            .define_operator("<>", OperatorDef::infix(4, Associativity::Left))
+            .unwrap();
+        let (expr, _) = Parser::from_lexer(lexer).parse_expr();
+        let expr = expr.unwrap();
+        assert_eq!(sexp(&expr), "(<> a b)");
+        assert_eq!(expr.span, Span::new(0, 6));
+    }
+
+    #[test]
+    fn same_precedence() {
+        let mut table = OperatorTable::prelude();
+        table
+            .define("**", OperatorDef::infix(11, Associativity::Left))
+            .unwrap();
+        table
+            .define("^^", OperatorDef::infix(11, Associativity::Right))
+            .unwrap();
+        table.define("++", OperatorDef::postfix(11)).unwrap();
+        let parse = |source| {
+            let lexer = Lexer::with_operators(source, table.clone());
+            let (expr, errors) = Parser::from_lexer(lexer).parse_expr();
+            assert!(errors.is_empty(), "{errors:?}");
+            sexp(&expr.unwrap())
+        };
+        assert_eq!(parse("-a ** b"), "(** (- a) b)");
+        assert_eq!(parse("-a ^^ b"), "(- (^^ a b))");
+        assert_eq!(parse("-a++"), "(post++ (- a))");
+        assert_eq!(parse("!-a ** b"), "(** (! (- a)) b)");
+    }
+
+    #[test]
+    fn expression_errors() {
+        assert_eq!(
+            errors("a < b < c"),
+            [ParseError::NonAssociative {
+                spelling: "<".to_string()
+            }]
+        );
+        assert!(errors("(a < b) < c").is_empty());
+        assert_eq!(
+            errors("* a"),
+            [ParseError::Expected {
+                expected: "an expression",
+                found: "`*`".to_string()
+            }]
+        );
+        assert_eq!(
+            errors("f(a b)"),
+            [ParseError::Expected {
+                expected: "`,` or `)`",
+                found: "`b`".to_string()
+            }]
+        );
+        assert_eq!(
+            errors("{ a b }"),
+            [ParseError::Expected {
+                expected: "`;` or `}`",
+                found: "`b`".to_string()
+            }]
+        );
+
+        let mut table = OperatorTable::prelude();
+        table
+            .define("?", OperatorDef::mixfix(0, Associativity::Right, ["!!"]))
+            .unwrap();
+        let lexer = Lexer::with_operators("a ? b", table);
+        let (_, errors) = Parser::from_lexer(lexer).parse_expr();
+        assert_eq!(
+            errors[0].value,
+            ParseError::ExpectedOperatorPart {
+                operator: "?".to_string(),
+                part: "!!".to_string()
+            }
+        );
+        assert_eq!(errors[0].span, Span::new(5, 5));
+    }
+}
@@ -0,0 +1,314 @@
+//! Type expressions.
+//!
+//! Types can take values as well as types as arguments, like the length in `Vec(T, n)`, so
+//! arguments and array lengths are parsed with the [expression parser](super::expr).
+
+use super::{ParseResult, Parser};
+use crate::ast::{FnTypeParam, Ident, PrimitiveType, Type, TypeArg, TypeKind};
+use osta_lexer::{Checkpoint, Span, Token, TokenKind};
+
+/// A snapshot of the parser, for trying a parse and going back if it doesn't fit.
+struct ParserCheckpoint<'src> {
+    lexer: Checkpoint<'src>,
+    errors: usize,
+    docs: usize,
+    inner_docs: usize,
+}
+
+/// Whether `token` is a run of `*`, which is split into pointers in types.
+fn is_pointer(token: &Token) -> bool {
+    matches!(token.kind, TokenKind::Operator(_)) && token.slice.bytes().all(|b| b == b'*')
+}
+
+impl<'src> Parser<'src> {
+    pub(super) fn ty(&mut self) -> ParseResult<Type<'src>> {
+        let Some(token) = self.peek() else {
+            return Err(self.unexpected("a type"));
+        };
+        if is_pointer(token) {
+            return self.pointer();
+        }
+        let primitive = match token.kind {
+            TokenKind::Identifier => return self.named(),
+            TokenKind::LBracket => return self.slice_or_array(),
+            TokenKind::Fn => return self.fn_type(),
+            TokenKind::Never => PrimitiveType::Never,
+            TokenKind::Void => PrimitiveType::Void,
+            TokenKind::IntType(width) => PrimitiveType::Int(width),
+            TokenKind::UintType(width) => PrimitiveType::Uint(width),
+            TokenKind::IsizeType => PrimitiveType::Isize,
+            TokenKind::UsizeType => PrimitiveType::Usize,
+            TokenKind::FloatType(width) => PrimitiveType::Float(width),
+            _ => return Err(self.unexpected("a type")),
+        };
+        Ok(Type {
+            kind: TypeKind::Primitive(primitive),
+            span: self.bump().span,
+        })
+    }
+
+    /// Parses `*T`, or `**T` and longer runs when they lex as a single operator.
+    fn pointer(&mut self) -> ParseResult<Type<'src>> {
+        let stars = self.bump().span;
+        let mut ty = self.ty()?;
+        for start in (stars.start..stars.end).rev() {
+            ty = Type {
+                span: Span::new(start, ty.span.end),
+                kind: TypeKind::Pointer(Box::new(ty)),
+            };
+        }
+        Ok(ty)
+    }
+
+    /// Parses a name, applied to arguments if followed by parentheses.
+    fn named(&mut self) -> ParseResult<Type<'src>> {
+        let name = self.ident("a type")?;
+        if self.eat(TokenKind::LParen).is_none() {
+            return Ok(Type {
+                kind: TypeKind::Named(name),
+                span: name.span,
+            });
+        }
+
+        let mut args = Vec::new();
+        while self.eat(TokenKind::RParen).is_none() {
+            args.push(self.type_arg()?);
+            if self.eat(TokenKind::Comma).is_none() {
+                self.expect(TokenKind::RParen, "`,` or `)`")?;
+                break;
+            }
+        }
+        Ok(Type {
+            kind: TypeKind::Apply { name, args },
+            span: name.span.to(self.lexer.prev_span()),
+        })
+    }
+
+    /// Parses an argument as a type if it is one, and as a value otherwise.
+    fn type_arg(&mut self) -> ParseResult<TypeArg<'src>> {
+        if self.starts_type() {
+            let checkpoint = self.checkpoint();
+            if let Ok(ty) = self.ty()
+                && (self.at(TokenKind::Comma) || self.at(TokenKind::RParen))
+            {
+                return Ok(TypeArg::Type(ty));
+            }
+            self.rewind(checkpoint);
+        }
+        Ok(TypeArg::Value(self.expr()?))
+    }
+
+    fn slice_or_array(&mut self) -> ParseResult<Type<'src>> {
+        let open = self.bump().span;
+        let elem = Box::new(self.ty()?);
+        let kind = match self.eat(TokenKind::Semicolon) {
+            Some(_) => {
+                let len = Box::new(self.expr()?);
+                self.expect(TokenKind::RBracket, "`]`")?;
+                TypeKind::Array { elem, len }
+            }
+            None => {
+                self.expect(TokenKind::RBracket, "`;` or `]`")?;
+                TypeKind::Slice(elem)
+            }
+        };
+        Ok(Type {
+            kind,
+            span: open.to(self.lexer.prev_span()),
+        })
+    }
+
+    fn fn_type(&mut self) -> ParseResult<Type<'src>> {
+        let start = self.bump().span;
+        self.expect(TokenKind::LParen, "`(`")?;
+        let mut params = Vec::new();
+        while self.eat(TokenKind::RParen).is_none() {
+            let name = self.param_name();
+            let ty = self.ty()?;
+            params.push(FnTypeParam { name, ty });
+            if self.eat(TokenKind::Comma).is_none() {
+                self.expect(TokenKind::RParen, "`,` or `)`")?;
+                break;
+            }
+        }
+        let ret = match self.eat(TokenKind::Arrow) {
+            Some(_) => Some(Box::new(self.ty()?)),
+            None => None,
+        };
+        Ok(Type {
+            kind: TypeKind::Fn { params, ret },
+            span: start.to(self.lexer.prev_span()),
+        })
+    }
+
+    /// Parses the `name:` of a named function type parameter, if there is one.
+    fn param_name(&mut self) -> Option<Ident<'src>> {
+        if !self.at(TokenKind::Identifier) {
+            return None;
+        }
+        let checkpoint = self.checkpoint();
+        let name = self.ident("a parameter name").ok()?;
+        if self.eat(TokenKind::Colon).is_some() {
+            return Some(name);
+        }
+        self.rewind(checkpoint);
+        None
+    }
+
+    fn starts_type(&mut self) -> bool {
+        self.peek().is_some_and(|token| {
+            is_pointer(token)
+                || matches!(
+                    token.kind,
+                    TokenKind::Identifier
+                        | TokenKind::LBracket
+                        | TokenKind::Fn
+                        | TokenKind::Never
+                        | TokenKind::Void
+                        | TokenKind::IntType(_)
+                        | TokenKind::UintType(_)
+                        | TokenKind::IsizeType
+                        | TokenKind::UsizeType
+                        | TokenKind::FloatType(_)
+                )
+        })
+    }
+
+    fn checkpoint(&self) -> ParserCheckpoint<'src> {
+        ParserCheckpoint {
+            lexer: self.lexer.checkpoint(),
+            errors: self.errors.len(),
+            docs: self.docs.len(),
+            inner_docs: self.inner_docs.len(),
+        }
+    }
+
+    /// Goes back to `checkpoint`, forgetting the errors and doc comments read since.
+    fn rewind(&mut self, checkpoint: ParserCheckpoint<'src>) {
+        self.lexer.rewind(checkpoint.lexer);
+        self.errors.truncate(checkpoint.errors);
+        self.docs.truncate(checkpoint.docs);
+        self.inner_docs.truncate(checkpoint.inner_docs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Parser;
+    use crate::ast::{ItemKind, PrimitiveType, Type, TypeArg, TypeKind};
+    use crate::parser::ParseError;
+    use osta_lexer::Span;
+
+    /// Renders `ty` back to source, with value arguments and lengths in braces.
+    fn render(source: &str, ty: &Type) -> String {
+        let join = |items: Vec<String>| items.join(", ");
+        match &ty.kind {
+            TypeKind::Primitive(primitive) => match primitive {
+                PrimitiveType::Never => "never".to_string(),
+                PrimitiveType::Void => "void".to_string(),
+                PrimitiveType::Int(width) => format!("i{width}"),
+                PrimitiveType::Uint(width) => format!("u{width}"),
+                PrimitiveType::Isize => "isize".to_string(),
+                PrimitiveType::Usize => "usize".to_string(),
+                PrimitiveType::Float(width) => format!("f{width}"),
+            },
+            TypeKind::Named(name) => name.name.to_string(),
+            TypeKind::Apply { name, args } => {
+                let args = args.iter().map(|arg| match arg {
+                    TypeArg::Type(ty) => render(source, ty),
+                    TypeArg::Value(expr) => format!("{{{}}}", &source[expr.span.range()]),
+                });
+                format!("{}({})", name.name, join(args.collect()))
+            }
+            TypeKind::Pointer(pointee) => format!("*{}", render(source, pointee)),
+            TypeKind::Slice(elem) => format!("[{}]", render(source, elem)),
+            TypeKind::Array { elem, len } => {
+                let len = &source[len.span.range()];
+                format!("[{}; {{{len}}}]", render(source, elem))
+            }
+            TypeKind::Fn { params, ret } => {
+                let params = params.iter().map(|param| match param.name {
+                    Some(name) => format!("{}: {}", name.name, render(source, &param.ty)),
+                    None => render(source, &param.ty),
+                });
+                let mut out = format!("fn({})", join(params.collect()));
+                if let Some(ret) = ret {
+                    out += &format!(" -> {}", render(source, ret));
+                }
+                out
+            }
+        }
+    }
+
+    /// Parses `source` as the type of a type alias.
+    fn parse(source: &str) -> Result<(String, Span), Vec<ParseError>> {
+        let alias = format!("type T = {source};");
+        let (file, errors) = Parser::new(&alias).parse_source_file();
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(|e| e.value).collect());
+        }
+        let ItemKind::TypeAlias(alias_item) = &file.items[0].kind else {
+            panic!("expected a type alias");
+        };
+        let ty = &alias_item.ty;
+        let span = Span::new(ty.span.start - 9, ty.span.end - 9);
+        Ok((render(&alias, ty), span))
+    }
+
+    fn render_type(source: &str) -> String {
+        parse(source).unwrap().0
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(render_type("i31"), "i31");
+        assert_eq!(render_type("u1"), "u1");
+        assert_eq!(render_type("f16"), "f16");
+        assert_eq!(render_type("never"), "never");
+    }
+
+    #[test]
+    fn compound_types() {
+        assert_eq!(render_type("*u8"), "*u8");
+        assert_eq!(render_type("** *void"), "***void");
+        assert_eq!(render_type("[*i32]"), "[*i32]");
+        assert_eq!(render_type("[[u8; 4]; N * 2]"), "[[u8; {4}]; {N * 2}]");
+        assert_eq!(
+            render_type("fn(i32, [u8]) -> fn() -> void"),
+            "fn(i32, [u8]) -> fn() -> void"
+        );
+        assert_eq!(render_type("fn(*T,)"), "fn(*T)");
+        assert_eq!(parse("*[T]").unwrap().1, Span::new(0, 4));
+    }
+
+    #[test]
+    fn dependent_types() {
+        assert_eq!(render_type("Vec(T, n)"), "Vec(T, n)");
+        assert_eq!(
+            render_type("Vec(Map(K, [V]), n + 1, 0x10)"),
+            "Vec(Map(K, [V]), {n + 1}, {0x10})"
+        );
+        assert_eq!(
+            render_type("Matrix(f32, rows(m), 4)"),
+            "Matrix(f32, rows(m), {4})"
+        );
+        assert_eq!(render_type("Unit()"), "Unit()");
+        assert_eq!(
+            render_type("fn(n: usize, xs: [i32; n]) -> Vec(i32, n)"),
+            "fn(n: usize, xs: [i32; {n}]) -> Vec(i32, n)"
+        );
+    }
+
+    #[test]
+    fn type_errors() {
+        let expected = |expected, found: &str| ParseError::Expected {
+            expected,
+            found: found.to_string(),
+        };
+        assert_eq!(parse("[u8; 4"), Err(vec![expected("`]`", "`;`")]));
+        assert_eq!(parse("[u8 4]"), Err(vec![expected("`;` or `]`", "`4`")]));
+        assert_eq!(parse("fn(i32"), Err(vec![expected("`,` or `)`", "`;`")]));
+        assert_eq!(parse("Vec(T n)"), Err(vec![expected("`,` or `)`", "`n`")]));
+        assert_eq!(parse("*"), Err(vec![expected("a type", "`;`")]));
+    }
+}
@@ -0,0 +1,196 @@
+//! Typed views over the untyped syntax tree.
+//!
+//! Each wrapper holds a [`SyntaxNode`] or [`SyntaxToken`] of the matching kind and exposes its
+//! children by role. Wrappers are as cheap to clone as the nodes they wrap, and never fail on
+//! malformed trees: missing children are returned as `None`.
+
+use crate::kind::{SyntaxKind, SyntaxNode, SyntaxToken};
+use osta_lexer::{Delimiter, Span, Token, TokenKind};
+
+pub trait AstNode: Sized {
+    fn can_cast(kind: SyntaxKind) -> bool;
+
+    fn cast(node: SyntaxNode) -> Option<Self>;
+
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+pub trait AstToken: Sized {
+    fn can_cast(kind: SyntaxKind) -> bool;
+
+    fn cast(token: SyntaxToken) -> Option<Self>;
+
+    fn syntax(&self) -> &SyntaxToken;
+
+    fn text(&self) -> &str {
+        self.syntax().text()
+    }
+}
+
+macro_rules! ast_node {
+    ($(#[$meta:meta])* $name:ident => $kind:pat) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub struct $name(SyntaxNode);
+
+        impl AstNode for $name {
+            fn can_cast(kind: SyntaxKind) -> bool {
+                matches!(kind, $kind)
+            }
+
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                Self::can_cast(node.kind()).then(|| Self(node))
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+macro_rules! ast_token {
+    ($(#[$meta:meta])* $name:ident => $kind:pat) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub struct $name(SyntaxToken);
+
+        impl AstToken for $name {
+            fn can_cast(kind: SyntaxKind) -> bool {
+                matches!(kind, $kind)
+            }
+
+            fn cast(token: SyntaxToken) -> Option<Self> {
+                Self::can_cast(token.kind()).then(|| Self(token))
+            }
+
+            fn syntax(&self) -> &SyntaxToken {
+                &self.0
+            }
+        }
+    };
+}
+
+/// Returns the children of `node` that cast to `N`.
+fn children<N: AstNode>(node: &SyntaxNode) -> impl Iterator<Item = N> {
+    node.children().filter_map(N::cast)
+}
+
+/// Returns the tokens in `node` that cast to `T`, without descending into child nodes.
+fn child_tokens<T: AstToken>(node: &SyntaxNode) -> impl Iterator<Item = T> {
+    node.children_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter_map(T::cast)
+}
+
+ast_node!(
+    /// The root of every tree.
+    SourceFile => SyntaxKind::SourceFile
+);
+
+impl SourceFile {
+    pub fn token_trees(&self) -> impl Iterator<Item = TokenTree> {
+        children(&self.0)
+    }
+
+    /// Returns the comments directly in the file, such as the `//!` docs of the file itself.
+    pub fn comments(&self) -> impl Iterator<Item = Comment> {
+        child_tokens(&self.0)
+    }
+}
+
+ast_node!(
+    /// Tokens in matching delimiters.
+    TokenTree => SyntaxKind::TokenTree
+);
+
+impl TokenTree {
+    pub fn delimiter(&self) -> Option<Delimiter> {
+        let open = self.0.first_token()?;
+        match open.kind() {
+            SyntaxKind::LParen => Some(Delimiter::Paren),
+            SyntaxKind::LBrace => Some(Delimiter::Brace),
+            SyntaxKind::LBracket => Some(Delimiter::Bracket),
+            _ => None,
+        }
+    }
+
+    /// Whether the tree ends with the delimiter matching its opening one.
+    pub fn is_closed(&self) -> bool {
+        let close = self.0.last_token().map(|token| token.kind());
+        let expected = match self.delimiter() {
+            Some(Delimiter::Paren) => SyntaxKind::RParen,
+            Some(Delimiter::Brace) => SyntaxKind::RBrace,
+            Some(Delimiter::Bracket) => SyntaxKind::RBracket,
+            None => return false,
+        };
+        self.0.children_with_tokens().count() > 1 && close == Some(expected)
+    }
+
+    pub fn token_trees(&self) -> impl Iterator<Item = TokenTree> {
+        children(&self.0)
+    }
+}
+
+ast_token!(
+    /// A comment of any kind, doc comments included.
+    Comment => SyntaxKind::Comment | SyntaxKind::OuterDocComment | SyntaxKind::InnerDocComment
+);
+
+impl Comment {
+    pub fn is_doc(&self) -> bool {
+        self.0.kind() != SyntaxKind::Comment
+    }
+
+    /// Returns the text of a doc comment without its markers, see [`Token::doc_text`].
+    pub fn doc_text(&self) -> Option<&str> {
+        let kind = match self.0.kind() {
+            SyntaxKind::OuterDocComment => TokenKind::OuterDocComment,
+            SyntaxKind::InnerDocComment => TokenKind::InnerDocComment,
+            _ => return None,
+        };
+        let range = self.0.text_range();
+        let span = Span::new(range.start().into(), range.end().into());
+        Token::new(kind, self.0.text(), span).doc_text()
+    }
+}
+
+ast_token!(Identifier => SyntaxKind::Identifier);
+
+#[cfg(test)]
+mod tests {
+    use super::{AstNode, AstToken, Comment, Identifier, TokenTree};
+    use crate::parse::parse_token_trees;
+    use osta_lexer::Delimiter;
+
+    #[test]
+    fn token_trees() {
+        let file = parse_token_trees("a { b [c] } (d").tree();
+        let trees: Vec<_> = file.token_trees().collect();
+        assert_eq!(trees.len(), 2);
+        assert_eq!(trees[0].delimiter(), Some(Delimiter::Brace));
+        assert!(trees[0].is_closed());
+        assert!(!trees[1].is_closed());
+
+        let inner = trees[0].token_trees().next().unwrap();
+        assert_eq!(inner.syntax().to_string(), "[c]");
+        let parent = inner.syntax().parent().and_then(TokenTree::cast);
+        assert_eq!(parent.as_ref(), Some(&trees[0]));
+        assert_eq!(u32::from(inner.syntax().text_range().start()), 6);
+    }
+
+    #[test]
+    fn tokens() {
+        let file = parse_token_trees("//! docs\n// plain\nname").tree();
+        let comments: Vec<_> = file.comments().collect();
+        assert_eq!(comments.len(), 2);
+        assert!(comments[0].is_doc());
+        assert_eq!(comments[0].doc_text(), Some(" docs"));
+        assert!(!comments[1].is_doc());
+        assert_eq!(comments[1].doc_text(), None);
+
+        let token = file.syntax().last_token().unwrap();
+        assert_eq!(Identifier::cast(token).unwrap().text(), "name");
+        assert!(Comment::cast(file.syntax().first_token().unwrap()).is_some());
+    }
+}
@@ -0,0 +1,215 @@
+use osta_lexer::TokenKind;
+
+/// The kind of a token or node in the syntax tree.
+///
+/// Token kinds mirror [`TokenKind`] without its payloads, which can be recomputed from the text
+/// of the token. Node kinds follow them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u16)]
+pub enum SyntaxKind {
+    // ======
+    // Tokens
+    // ======
+    // Trivia
+    Whitespace,
+    Newline,
+    Comment,
+    OuterDocComment,
+    InnerDocComment,
+    Operator,
+    // Keywords
+    Const,
+    Static,
+    Pub,
+    Extern,
+    Comptime,
+    Linear,
+    Fn,
+    Type,
+    Struct,
+    Enum,
+    Union,
+    Import,
+    Let,
+    If,
+    Else,
+    While,
+    For,
+    Return,
+    True,
+    False,
+    Null,
+    Never,
+    Void,
+    UintType,
+    UsizeType,
+    IntType,
+    IsizeType,
+    FloatType,
+    // Identifiers
+    Identifier,
+    MacroIdentifier,
+    ComptimeIdentifier,
+    DirectiveIdentifier,
+    // Literals
+    DecInt,
+    BinInt,
+    OctInt,
+    HexInt,
+    Float,
+    IntFloat,
+    FloatExp,
+    IntExp,
+    HexFloat,
+    BinFloat,
+    String,
+    ByteString,
+    RawString,
+    Char,
+    Byte,
+    // Symbols
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Comma,
+    Colon,
+    Semicolon,
+    Arrow,
+    FatArrow,
+    Dollar,
+    /// Source text the lexer couldn't turn into a token.
+    ErrorToken,
+
+    // =====
+    // Nodes
+    // =====
+    SourceFile,
+    /// Tokens enclosed in matching delimiters, including the delimiters.
+    TokenTree,
+    /// Tokens that don't fit where they were found.
+    Error,
+}
+
+impl SyntaxKind {
+    const LAST: SyntaxKind = SyntaxKind::Error;
+
+    pub fn is_token(self) -> bool {
+        self <= SyntaxKind::ErrorToken
+    }
+
+    /// Whether the token carries no meaning for the parser, see [`TokenKind::is_trivia`].
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment
+        )
+    }
+
+    pub(crate) fn from_raw(raw: u16) -> Self {
+        assert!(raw <= SyntaxKind::LAST as u16, "invalid syntax kind {raw}");
+        // SAFETY: `SyntaxKind` is a fieldless `repr(u16)` enum and `raw` is in range.
+        unsafe { std::mem::transmute::<u16, SyntaxKind>(raw) }
+    }
+}
+
+impl From<&TokenKind> for SyntaxKind {
+    fn from(kind: &TokenKind) -> Self {
+        match kind {
+            TokenKind::Whitespace => SyntaxKind::Whitespace,
+            TokenKind::Newline => SyntaxKind::Newline,
+            TokenKind::Comment => SyntaxKind::Comment,
+            TokenKind::OuterDocComment => SyntaxKind::OuterDocComment,
+            TokenKind::InnerDocComment => SyntaxKind::InnerDocComment,
+            TokenKind::Operator(_) => SyntaxKind::Operator,
+            TokenKind::Const => SyntaxKind::Const,
+            TokenKind::Static => SyntaxKind::Static,
+            TokenKind::Pub => SyntaxKind::Pub,
+            TokenKind::Extern => SyntaxKind::Extern,
+            TokenKind::Comptime => SyntaxKind::Comptime,
+            TokenKind::Linear => SyntaxKind::Linear,
+            TokenKind::Fn => SyntaxKind::Fn,
+            TokenKind::Type => SyntaxKind::Type,
+            TokenKind::Struct => SyntaxKind::Struct,
+            TokenKind::Enum => SyntaxKind::Enum,
+            TokenKind::Union => SyntaxKind::Union,
+            TokenKind::Import => SyntaxKind::Import,
+            TokenKind::Let => SyntaxKind::Let,
+            TokenKind::If => SyntaxKind::If,
+            TokenKind::Else => SyntaxKind::Else,
+            TokenKind::While => SyntaxKind::While,
+            TokenKind::For => SyntaxKind::For,
+            TokenKind::Return => SyntaxKind::Return,
+            TokenKind::True => SyntaxKind::True,
+            TokenKind::False => SyntaxKind::False,
+            TokenKind::Null => SyntaxKind::Null,
+            TokenKind::Never => SyntaxKind::Never,
+            TokenKind::Void => SyntaxKind::Void,
+            TokenKind::UintType(_) => SyntaxKind::UintType,
+            TokenKind::UsizeType => SyntaxKind::UsizeType,
+            TokenKind::IntType(_) => SyntaxKind::IntType,
+            TokenKind::IsizeType => SyntaxKind::IsizeType,
+            TokenKind::FloatType(_) => SyntaxKind::FloatType,
+            TokenKind::Identifier => SyntaxKind::Identifier,
+            TokenKind::MacroIdentifier => SyntaxKind::MacroIdentifier,
+            TokenKind::ComptimeIdentifier => SyntaxKind::ComptimeIdentifier,
+            TokenKind::DirectiveIdentifier => SyntaxKind::DirectiveIdentifier,
+            TokenKind::DecInt(_) => SyntaxKind::DecInt,
+            TokenKind::BinInt(_) => SyntaxKind::BinInt,
+            TokenKind::OctInt(_) => SyntaxKind::OctInt,
+            TokenKind::HexInt(_) => SyntaxKind::HexInt,
+            TokenKind::Float(_) => SyntaxKind::Float,
+            TokenKind::IntFloat => SyntaxKind::IntFloat,
+            TokenKind::FloatExp(_) => SyntaxKind::FloatExp,
+            TokenKind::IntExp(_) => SyntaxKind::IntExp,
+            TokenKind::HexFloat(_) => SyntaxKind::HexFloat,
+            TokenKind::BinFloat(_) => SyntaxKind::BinFloat,
+            TokenKind::String => SyntaxKind::String,
+            TokenKind::ByteString => SyntaxKind::ByteString,
+            TokenKind::RawString => SyntaxKind::RawString,
+            TokenKind::Char => SyntaxKind::Char,
+            TokenKind::Byte => SyntaxKind::Byte,
+            TokenKind::LParen => SyntaxKind::LParen,
+            TokenKind::RParen => SyntaxKind::RParen,
+            TokenKind::LBrace => SyntaxKind::LBrace,
+            TokenKind::RBrace => SyntaxKind::RBrace,
+            TokenKind::LBracket => SyntaxKind::LBracket,
+            TokenKind::RBracket => SyntaxKind::RBracket,
+            TokenKind::Comma => SyntaxKind::Comma,
+            TokenKind::Colon => SyntaxKind::Colon,
+            TokenKind::Semicolon => SyntaxKind::Semicolon,
+            TokenKind::Arrow => SyntaxKind::Arrow,
+            TokenKind::FatArrow => SyntaxKind::FatArrow,
+            TokenKind::Dollar => SyntaxKind::Dollar,
+        }
+    }
+}
+
+impl From<SyntaxKind> for rowan::SyntaxKind {
+    fn from(kind: SyntaxKind) -> Self {
+        rowan::SyntaxKind(kind as u16)
+    }
+}
+
+/// The [`rowan::Language`] of Osta syntax trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OstaLanguage {}
+
+impl rowan::Language for OstaLanguage {
+    type Kind = SyntaxKind;
+
+    fn kind_from_raw(raw: rowan::SyntaxKind) -> SyntaxKind {
+        SyntaxKind::from_raw(raw.0)
+    }
+
+    fn kind_to_raw(kind: SyntaxKind) -> rowan::SyntaxKind {
+        kind.into()
+    }
+}
+
+pub type SyntaxNode = rowan::SyntaxNode<OstaLanguage>;
+pub type SyntaxToken = rowan::SyntaxToken<OstaLanguage>;
+pub type SyntaxElement = rowan::SyntaxElement<OstaLanguage>;
+pub type SyntaxNodeChildren = rowan::SyntaxNodeChildren<OstaLanguage>;
@@ -0,0 +1,30 @@
+//! A lossless syntax tree for Osta sources.
+//!
+//! Trees are built on [`rowan`]: immutable green nodes that can be shared between versions of a
+//! file, with a red layer on top that adds parent pointers and text ranges. Every byte of the
+//! source, trivia and invalid tokens included, belongs to exactly one token of the tree.
+
+pub mod ast;
+mod kind;
+mod parse;
+
+pub use kind::{
+    OstaLanguage, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxNodeChildren, SyntaxToken,
+};
+pub use parse::{Parse, SyntaxError, parse_token_trees};
+pub use rowan::{GreenNode, TextRange, TextSize};
+
+use osta_lexer::Span;
+
+/// Converts a lexer span to a tree range.
+pub fn text_range(span: Span) -> TextRange {
+    TextRange::new(
+        TextSize::new(span.start as u32),
+        TextSize::new(span.end as u32),
+    )
+}
+
+/// Converts a tree range to a lexer span.
+pub fn span(range: TextRange) -> Span {
+    Span::new(range.start().into(), range.end().into())
+}
@@ -0,0 +1,240 @@
+use crate::ast::{AstNode, SourceFile};
+use crate::kind::{SyntaxKind, SyntaxNode};
+use osta_lexer::{Delimiter, Lexer, LexerError, Span, Spanned};
+use rowan::{GreenNode, GreenNodeBuilder};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum SyntaxError {
+    #[error(transparent)]
+    Lexer(#[from] LexerError),
+}
+
+/// A syntax tree, along with the errors found while building it.
+///
+/// The tree covers the source exactly, errors included, so its text is always the source it was
+/// built from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parse {
+    green: GreenNode,
+    errors: Vec<Spanned<SyntaxError>>,
+}
+
+impl Parse {
+    pub fn new(green: GreenNode, errors: Vec<Spanned<SyntaxError>>) -> Self {
+        Self { green, errors }
+    }
+
+    pub fn green(&self) -> &GreenNode {
+        &self.green
+    }
+
+    /// Returns the root of the tree. Nodes are created lazily, so this is cheap.
+    pub fn syntax_node(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    pub fn tree(&self) -> SourceFile {
+        SourceFile::cast(self.syntax_node()).expect("the root is always a source file")
+    }
+
+    /// Returns the errors, ordered by where they start.
+    pub fn errors(&self) -> &[Spanned<SyntaxError>] {
+        &self.errors
+    }
+}
+
+/// Builds a tree of the tokens of `source`, trivia included, grouped by their delimiters.
+///
+/// Operators are split with [`osta_lexer::OperatorTable::prelude`]. Unbalanced delimiters are
+/// recovered from like in [`osta_lexer::TokenTree::parse_all`], except that closing delimiters
+/// that match no group are kept in an [`SyntaxKind::Error`] node.
+pub fn parse_token_trees(source: &str) -> Parse {
+    let lexer = Lexer::with_trivia(source);
+
+    let mut builder = GreenNodeBuilder::new();
+    let mut errors = Vec::new();
+    let mut error = |error: LexerError, span| errors.push(Spanned::new(error.into(), span));
+    let mut stack: Vec<(Delimiter, Span)> = Vec::new();
+    // The end of the text added to the tree so far
+    let mut end = 0;
+
+    builder.start_node(SyntaxKind::SourceFile.into());
+    for result in lexer {
+        let token = match result {
+            Ok(token) => token,
+            Err(e) => {
+                // Only add the text not covered yet, so overlapping errors don't repeat it
+                if e.span.end > end {
+                    let text = &source[e.span.start.max(end)..e.span.end];
+                    builder.token(SyntaxKind::ErrorToken.into(), text);
+                    end = e.span.end;
+                }
+                error(e.value, e.span);
+                continue;
+            }
+        };
+        end = token.span.end;
+        let kind = SyntaxKind::from(&token.kind).into();
+
+        if let Some(delimiter) = Delimiter::from_open(&token.kind) {
+            builder.start_node(SyntaxKind::TokenTree.into());
+            builder.token(kind, token.slice);
+            stack.push((delimiter, token.span));
+            continue;
+        }
+        let Some(delimiter) = Delimiter::from_close(&token.kind) else {
+            builder.token(kind, token.slice);
+            continue;
+        };
+        let close = token.span;
+        let Some(depth) = stack.iter().rposition(|(open, _)| *open == delimiter) else {
+            builder.start_node(SyntaxKind::Error.into());
+            builder.token(kind, token.slice);
+            builder.finish_node();
+            error(LexerError::UnexpectedClosingDelimiter { delimiter }, close);
+            continue;
+        };
+
+        for (expected, open) in stack.drain(depth + 1..).rev() {
+            let mismatched = LexerError::MismatchedDelimiter {
+                expected,
+                found: delimiter,
+                open,
+                close,
+            };
+            error(mismatched, close);
+            builder.finish_node();
+        }
+        stack.pop();
+        builder.token(kind, token.slice);
+        builder.finish_node();
+    }
+
+    for (delimiter, open) in stack.into_iter().rev() {
+        error(LexerError::UnclosedDelimiter { delimiter, open }, open);
+        builder.finish_node();
+    }
+    builder.finish_node();
+
+    errors.sort_by_key(|error| error.span.start);
+    Parse::new(builder.finish(), errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SyntaxError, parse_token_trees};
+    use crate::ast::AstNode;
+    use crate::kind::{SyntaxKind, SyntaxNode};
+    use osta_lexer::{Delimiter, LexerError, Span};
+    use rowan::{NodeOrToken, WalkEvent};
+
+    /// Renders the tree with one element per line, indented by depth.
+    fn debug_tree(node: &SyntaxNode) -> String {
+        let mut out = String::new();
+        let mut depth = 0;
+        for event in node.preorder_with_tokens() {
+            match event {
+                WalkEvent::Enter(element) => {
+                    let (kind, range) = (element.kind(), element.text_range());
+                    out += &format!("{:depth$}{kind:?}@{range:?}", "", depth = depth * 2);
+                    match element {
+                        NodeOrToken::Node(_) => depth += 1,
+                        NodeOrToken::Token(token) => out += &format!(" {:?}", token.text()),
+                    }
+                    out.push('\n');
+                }
+                WalkEvent::Leave(element) => {
+                    if element.as_node().is_some() {
+                        depth -= 1;
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn lossless() {
+        let sources = [
+            "",
+            "const x = (1 + [2, 3]) // comment\n",
+            "/* nested /* comment */ */\tfn\r\n  §§ \"open",
+            "a ) { b ( ] c",
+            "r#\"raw\"# b'x' 0x1p3 @m($x) $if",
+        ];
+        for source in sources {
+            let parse = parse_token_trees(source);
+            assert_eq!(parse.syntax_node().to_string(), source);
+        }
+    }
+
+    #[test]
+    fn several_errors_in_one_literal() {
+        for source in [r#""\q\z""#, r#"x "\q\u{D800}\z" b"é\q" '\q'"#] {
+            let parse = parse_token_trees(source);
+            assert!(parse.errors().len() >= 2);
+            assert_eq!(parse.tree().syntax().to_string(), source);
+        }
+    }
+
+    #[test]
+    fn token_trees() {
+        let parse = parse_token_trees("f(a, [b]) // done");
+        assert!(parse.errors().is_empty());
+        assert_eq!(
+            debug_tree(&parse.syntax_node()),
+            r#"SourceFile@0..17
+  Identifier@0..1 "f"
+  TokenTree@1..9
+    LParen@1..2 "("
+    Identifier@2..3 "a"
+    Comma@3..4 ","
+    Whitespace@4..5 " "
+    TokenTree@5..8
+      LBracket@5..6 "["
+      Identifier@6..7 "b"
+      RBracket@7..8 "]"
+    RParen@8..9 ")"
+  Whitespace@9..10 " "
+  Comment@10..17 "// done"
+"#
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let parse = parse_token_trees("] § (a");
+        assert_eq!(
+            debug_tree(&parse.syntax_node()),
+            r#"SourceFile@0..7
+  Error@0..1
+    RBracket@0..1 "]"
+  Whitespace@1..2 " "
+  ErrorToken@2..4 "§"
+  Whitespace@4..5 " "
+  TokenTree@5..7
+    LParen@5..6 "("
+    Identifier@6..7 "a"
+"#
+        );
+        let errors: Vec<_> = parse.errors().iter().map(|e| &e.value).collect();
+        assert_eq!(
+            errors,
+            [
+                &SyntaxError::Lexer(LexerError::UnexpectedClosingDelimiter {
+                    delimiter: Delimiter::Bracket,
+                }),
+                &SyntaxError::Lexer(LexerError::UnexpectedChar {
+                    ch: '§',
+                    span: Span::new(2, 4),
+                }),
+                &SyntaxError::Lexer(LexerError::UnclosedDelimiter {
+                    delimiter: Delimiter::Paren,
+                    open: Span::new(5, 6),
+                }),
+            ]
+        );
+        assert_eq!(parse.syntax_node().kind(), SyntaxKind::SourceFile);
+    }
+}
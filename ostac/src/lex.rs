@@ -0,0 +1,213 @@
+use clap::{Args, ValueEnum};
+use osta_diagnostics::{Diagnostic, JsonEmitter, Renderer};
+use osta_lexer::{Edition, Encoding, Lexer, LineIndex, Span, Token, source_edition};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct LexArgs {
+    /// The file to lex, or `-` for standard input.
+    pub file: PathBuf,
+    /// How to print tokens and errors.
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+    /// Also print whitespace and newline tokens.
+    #[arg(long)]
+    pub trivia: bool,
+    /// The edition selecting which words are keywords, unless the file starts with `$edition`.
+    #[arg(long, default_value_t = Edition::LATEST)]
+    pub edition: Edition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Aligned columns, with errors rendered as source snippets.
+    Human,
+    /// One JSON object per line, with errors in the diagnostic JSON format.
+    Json,
+    /// One unpadded line per token, for grepping and diffing.
+    Compact,
+}
+
+#[derive(Serialize)]
+struct JsonToken<'a> {
+    kind: String,
+    start: usize,
+    end: usize,
+    line: usize,
+    col: usize,
+    slice: &'a str,
+}
+
+/// Prints every token of `source` to `out` and every error to `err`.
+///
+/// Returns whether the source lexed without errors.
+pub fn run(
+    args: &LexArgs,
+    source: &str,
+    path: &str,
+    renderer: Renderer,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> io::Result<bool> {
+    let index = LineIndex::new(source);
+    let (edition, edition_error) = match source_edition(source) {
+        Some(Ok(edition)) => (edition, None),
+        Some(Err(error)) => (args.edition, Some(Err(error))),
+        None => (args.edition, None),
+    };
+    let mut lexer = Lexer::new(source);
+    lexer.set_trivia(args.trivia);
+    lexer.set_edition(edition);
+
+    let mut ok = true;
+    for result in edition_error.into_iter().chain(lexer) {
+        match result {
+            Ok(token) => print_token(args.format, &index, &token, out)?,
+            Err(error) => {
+                ok = false;
+                let diagnostic = Diagnostic::from(error);
+                match args.format {
+                    Format::Json => JsonEmitter::new().emit(err, &diagnostic, &index, path)?,
+                    _ => renderer.emit(err, &diagnostic, &index, path)?,
+                }
+            }
+        }
+    }
+    Ok(ok)
+}
+
+fn print_token(
+    format: Format,
+    index: &LineIndex,
+    token: &Token,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let Span { start, end } = token.span;
+    let pos = index.line_col(start, Encoding::Utf32).unwrap_or_default();
+    let (line, col) = (pos.line + 1, pos.col + 1);
+    let kind = format!("{:?}", token.kind);
+
+    match format {
+        Format::Human => {
+            let location = format!("{line}:{col}");
+            let span = format!("{start}..{end}");
+            writeln!(out, "{location:<8} {span:<12} {kind:<24} {:?}", token.slice)
+        }
+        Format::Json => {
+            let token = JsonToken {
+                kind,
+                start,
+                end,
+                line,
+                col,
+                slice: token.slice,
+            };
+            writeln!(out, "{}", serde_json::to_string(&token)?)
+        }
+        Format::Compact => writeln!(out, "{line}:{col} {start}..{end} {kind} {:?}", token.slice),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, LexArgs, run};
+    use osta_diagnostics::Renderer;
+    use osta_lexer::{Edition, OperatorTable};
+
+    fn lex(source: &str, format: Format) -> (bool, String, String) {
+        let args = LexArgs {
+            file: "test.osta".into(),
+            format,
+            trivia: false,
+            edition: Edition::LATEST,
+        };
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let ok = run(
+            &args,
+            source,
+            "test.osta",
+            Renderer::plain(),
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        let string = |bytes| String::from_utf8(bytes).unwrap();
+        (ok, string(out), string(err))
+    }
+
+    /// The kind of the prelude operator `spelling`, as printed.
+    fn operator(spelling: &str) -> String {
+        let id = OperatorTable::prelude().get(spelling).unwrap();
+        format!("Operator({id})")
+    }
+
+    #[test]
+    fn human() {
+        let (ok, out, err) = lex("const x\n  1._0e-10", Format::Human);
+        assert!(ok);
+        assert_eq!(err, "");
+        assert_eq!(
+            out,
+            format!(
+                "\
+1:1      0..5         Const                    \"const\"
+1:7      6..7         Identifier               \"x\"
+2:3      10..12       IntFloat                 \"1.\"
+2:5      12..15       Identifier               \"_0e\"
+2:8      15..16       {:<24} \"-\"
+2:9      16..18       DecInt(None)             \"10\"
+",
+                operator("-")
+            )
+        );
+    }
+
+    #[test]
+    fn compact() {
+        let (ok, out, _) = lex("a + 1", Format::Compact);
+        assert!(ok);
+        assert_eq!(
+            out,
+            format!(
+                "1:1 0..1 Identifier \"a\"\n1:3 2..3 {} \"+\"\n1:5 4..5 DecInt(None) \"1\"\n",
+                operator("+")
+            )
+        );
+    }
+
+    #[test]
+    fn edition_directive() {
+        let (ok, out, _) = lex("$edition(2024) fn", Format::Compact);
+        assert!(ok);
+        assert!(out.ends_with("1:16 15..17 Identifier \"fn\"\n"));
+
+        let (ok, out, err) = lex("$edition(2023) fn", Format::Compact);
+        assert!(!ok);
+        assert!(err.contains("unknown edition `2023`"));
+        assert!(out.ends_with("Fn \"fn\"\n"));
+    }
+
+    #[test]
+    fn json() {
+        let (ok, out, err) = lex("é §", Format::Json);
+        assert!(!ok);
+        assert_eq!(
+            out,
+            r#"{"kind":"Identifier","start":0,"end":2,"line":1,"col":1,"slice":"é"}"#.to_owned()
+                + "\n"
+        );
+        let error: serde_json::Value = serde_json::from_str(&err).unwrap();
+        assert_eq!(error["code"]["code"], "E0010");
+        assert_eq!(error["spans"][0]["column_start"], 3);
+    }
+
+    #[test]
+    fn errors() {
+        let (ok, out, err) = lex("a § b", Format::Human);
+        assert!(!ok);
+        assert_eq!(out.lines().count(), 2);
+        assert!(err.starts_with("error[E0010]: unexpected character `§`\n --> test.osta:1:3\n"));
+    }
+}
@@ -0,0 +1,56 @@
+mod lex;
+
+use clap::{Parser, Subcommand};
+use osta_diagnostics::{Diagnostic, Renderer};
+use osta_lexer::LineIndex;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// The Osta compiler.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the tokens of a source file.
+    Lex(lex::LexArgs),
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Command::Lex(args) => read_source(&args.file).and_then(|source| {
+            let path = args.file.display().to_string();
+            let renderer = Renderer::for_stream(&io::stderr());
+            let (mut out, mut err) = (io::stdout().lock(), io::stderr().lock());
+            lex::run(args, &source, &path, renderer, &mut out, &mut err)
+        }),
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            let diagnostic = Diagnostic::error(error.to_string());
+            let renderer = Renderer::for_stream(&io::stderr());
+            eprint!("{}", renderer.render(&diagnostic, &LineIndex::new(""), ""));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Reads the file at `path`, or standard input if it is `-`.
+fn read_source(path: &PathBuf) -> io::Result<String> {
+    if path.as_os_str() == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        return Ok(source);
+    }
+    std::fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("couldn't read `{}`: {e}", path.display())))
+}